@@ -1,18 +1,31 @@
+use crate::config::Config;
+use crate::fprint::{Fprint, Scan};
+use crate::graphics::overlay::Overlay;
 use crate::graphics::Graphics;
-use crate::lock::LockState;
+use crate::handlers::{pointer::Cursor, seat::Seat};
+use crate::lock::{AuthState, LockState};
 use crate::media::Media;
+use crate::widgets::{Frame, LockView};
 use smithay_client_toolkit::{
     compositor::CompositorState,
+    data_device_manager::DataDeviceManagerState,
     output::OutputState,
     reexports::{
-        calloop::{EventLoop as CEventLoop, LoopHandle},
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            EventLoop as CEventLoop, LoopHandle,
+        },
+        calloop_wayland_source::WaylandSource,
         client::{
-            globals::registry_queue_init, protocol::wl_keyboard::WlKeyboard, Connection,
-            QueueHandle,
+            globals::registry_queue_init,
+            protocol::{wl_seat::WlSeat, wl_surface::WlSurface},
+            Connection, QueueHandle,
         },
+        protocols::wp::text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        protocols_wlr::output_power_management::v1::client::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
     },
     registry::RegistryState,
-    seat::SeatState,
+    seat::{pointer::cursor_shape::CursorShapeManager, SeatState},
     session_lock::SessionLockState,
 };
 use wayland_client::protocol::wl_compositor;
@@ -26,7 +39,18 @@ pub struct Wayland {
     pub registry_state: RegistryState,
     pub output_state: OutputState,
     pub seat_state: SeatState,
-    pub keyboard: Option<WlKeyboard>,
+    pub seats: Vec<Seat>,
+    /// Seat that sent the last key press, whose clipboard Ctrl+V pastes.
+    pub active_seat: Option<WlSeat>,
+    /// Only bound when pasting into the password field is allowed.
+    pub data_device_manager: Option<DataDeviceManagerState>,
+    /// For input methods, when the compositor supports text-input-v3.
+    pub text_input_manager: Option<ZwpTextInputManagerV3>,
+    pub cursor: Cursor,
+    pub cursor_shape_manager: Option<CursorShapeManager>,
+    /// Only bound when idle outputs are turned off.
+    pub output_power_manager: Option<ZwlrOutputPowerManagerV1>,
+    pub queue_handle: QueueHandle<AppData>,
 }
 
 pub struct AppData {
@@ -38,13 +62,48 @@ pub struct AppData {
 }
 
 impl AppData {
-    pub fn connect(base: BaseDirectories) {
+    /// Locks the session and runs until it is unlocked. `idle` says an idle
+    /// daemon started the lock rather than the user.
+    pub fn connect(base: BaseDirectories, idle: bool) {
         let conn = Connection::connect_to_env().unwrap();
+        let config = Config::load(&base);
 
-        let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+        let (globals, event_queue) = registry_queue_init(&conn).unwrap();
         let qh: QueueHandle<AppData> = event_queue.handle();
-        let event_loop: CEventLoop<AppData> =
+        let mut event_loop: CEventLoop<AppData> =
             CEventLoop::try_new().expect("Failed to initialize the event loop!");
+        let lock_data = LockState::from_lock(
+            SessionLockState::new(&globals, &qh)
+                .lock(&qh)
+                .expect("ext-session-lock not supported"),
+            Media::from_config(&config, &base),
+            &config,
+            idle,
+        );
+        let data_device_manager = if lock_data.keyboard.allow_paste {
+            DataDeviceManagerState::bind(&globals, &qh)
+                .map_err(|e| tracing::warn!("Pasting is unavailable: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        let cursor = config
+            .get("pointer", "cursor")
+            .map(|s| {
+                s.parse::<Cursor>().unwrap_or_else(|_| {
+                    tracing::warn!("Unknown cursor '{}', expected 'default' or 'hidden'", s);
+                    Cursor::Default
+                })
+            })
+            .unwrap_or(Cursor::Default);
+        let output_power_manager = if lock_data.idle.turns_off() {
+            globals
+                .bind(&qh, 1..=1, ())
+                .map_err(|e| tracing::warn!("Outputs can't be turned off while idle: {}", e))
+                .ok()
+        } else {
+            None
+        };
         let mut app_data = AppData {
             xdg: base.clone(),
             wayland: Wayland {
@@ -55,21 +114,27 @@ impl AppData {
                 registry_state: RegistryState::new(&globals),
                 output_state: OutputState::new(&globals, &qh),
                 seat_state: SeatState::new(&globals, &qh),
-                keyboard: None,
+                seats: Vec::new(),
+                active_seat: None,
+                data_device_manager,
+                text_input_manager: globals.bind(&qh, 1..=1, ()).ok(),
+                cursor,
+                cursor_shape_manager: CursorShapeManager::bind(&globals, &qh).ok(),
+                output_power_manager,
+                queue_handle: qh.clone(),
             },
             graphics_context: Graphics::new(),
-            lock_data: LockState::from_lock(
-                SessionLockState::new(&globals, &qh)
-                    .lock(&qh)
-                    .expect("ext-session-lock not supported"),
-                Media::from_config(&base),
-            ),
+            lock_data,
             exit: false,
         };
+        WaylandSource::new(conn.clone(), event_queue)
+            .insert(event_loop.handle())
+            .expect("Failed to insert the wayland source!");
+        app_data.schedule_updates();
         tracing::trace!("Initiating lock");
         conn.roundtrip().unwrap();
         loop {
-            event_queue.blocking_dispatch(&mut app_data).unwrap();
+            event_loop.dispatch(None, &mut app_data).unwrap();
 
             if app_data.exit {
                 break;
@@ -78,4 +143,241 @@ impl AppData {
     }
 }
 
+impl AppData {
+    /// Sizes the surface at `index` for a `logical` configure size and sets up
+    /// its media for the resulting physical size.
+    pub fn configure_surface(&mut self, index: usize, logical: (u32, u32)) {
+        let surface = &mut self.lock_data.session_lock_surfaces[index];
+        let scale = surface.scale_factor();
+        let (width, height) = (logical.0 * scale, logical.1 * scale);
+        surface.resize(&self.graphics_context, (width, height));
+
+        let mut media = std::mem::replace(&mut surface.media, Media::None);
+        match media {
+            Media::Image(ref mut im) => {
+                im.resize(width, height);
+                self.graphics_context
+                    .create_texture_from_image_for_surface(surface, im);
+            }
+            Media::Shader(ref path) => {
+                self.graphics_context
+                    .create_texture_from_shader_for_surface(surface, path);
+            }
+            Media::Solid(_) => {}
+            _ => {
+                tracing::trace!("Screenshot, Video not supported yet!");
+            }
+        }
+        surface.media = media;
+        surface.set_configured();
+    }
+
+    /// Draws the current frame of the surface at `index`.
+    pub fn render_surface(&mut self, index: usize) {
+        let surface = &self.lock_data.session_lock_surfaces[index];
+        if !surface.is_configured() {
+            return;
+        }
+        let keyboard = self.active_keyboard();
+        let layout = keyboard.and_then(|state| state.layout_name());
+        let lock = LockView {
+            auth_state: self.lock_data.auth_state,
+            password_length: self.lock_data.password_buffer.chars().count(),
+            caps_lock: keyboard.is_some_and(|state| state.caps_lock),
+            num_lock: keyboard.is_some_and(|state| state.num_lock),
+            layout: layout.as_deref(),
+            layouts: keyboard.map_or(0, |state| state.num_layouts()),
+            feedback: &self.lock_data.feedback,
+            fingerprint: self
+                .lock_data
+                .fingerprint
+                .as_ref()
+                .filter(|fprint| fprint.is_verifying())
+                .map(|_| self.lock_data.fingerprint_prompt.as_str()),
+            revealed: self
+                .lock_data
+                .reveal
+                .then_some(self.lock_data.password_buffer.as_str()),
+            physical_keyboard: self.has_keyboard(),
+            touched: self.lock_data.touched,
+            keypad_shift: self.lock_data.keypad_shift,
+        };
+        let mut frame = Frame {
+            text: self.graphics_context.text(),
+            lock: &lock,
+            surface_size: surface.size(),
+            scale_factor: surface.scale_factor(),
+        };
+        let mut overlay = Overlay {
+            dim: self.lock_data.idle.dim.max(self.lock_data.transition.dim()),
+            ..Default::default()
+        };
+        let animating = self.lock_data.transition.is_running() && !surface.frame_pending;
+        if animating {
+            // Asks for the next frame, committed along with this one.
+            let wl_surface = surface.wl_surface();
+            wl_surface.frame(&self.wayland.queue_handle, wl_surface.clone());
+        }
+        self.lock_data
+            .layout
+            .draw(&mut frame, surface.output_name(), &mut overlay);
+        // Nothing was committed if rendering failed, so no frame callback
+        // is coming and the next render has to ask again.
+        let rendered = match self
+            .graphics_context
+            .render(surface, &surface.media, &overlay)
+        {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Unable to render frame: {}", e);
+                false
+            }
+        };
+        let surface = &mut self.lock_data.session_lock_surfaces[index];
+        surface.targets = overlay.targets;
+        surface.frame_pending |= animating && rendered;
+    }
+
+    pub fn render_all(&mut self) {
+        for index in 0..self.lock_data.session_lock_surfaces.len() {
+            self.render_surface(index);
+        }
+    }
+
+    /// Switches the indicator to `state`, redraws, and arms a timer that
+    /// returns it to `Idle` if the state is only shown briefly.
+    pub fn set_auth_state(&mut self, state: AuthState) {
+        if let Some(token) = self.lock_data.auth_state_timer.take() {
+            self.wayland.loop_handle.remove(token);
+        }
+        self.lock_data.auth_state = state;
+        if let Some(timeout) = state.timeout() {
+            let token = self
+                .wayland
+                .loop_handle
+                .insert_source(Timer::from_duration(timeout), |_, _, app_data| {
+                    app_data.lock_data.auth_state_timer = None;
+                    app_data.lock_data.auth_state = AuthState::Idle;
+                    app_data.render_all();
+                    TimeoutAction::Drop
+                })
+                .expect("Failed to insert the indicator timer!");
+            self.lock_data.auth_state_timer = Some(token);
+        }
+        self.render_all();
+    }
+
+    /// Arms the timer that clears `feedback` once the message timeout runs
+    /// out. The caller redraws.
+    pub fn show_feedback(&mut self) {
+        if let Some(token) = self.lock_data.feedback_timer.take() {
+            self.wayland.loop_handle.remove(token);
+        }
+        let Some(timeout) = self.lock_data.feedback_timeout else {
+            return;
+        };
+        let token = self
+            .wayland
+            .loop_handle
+            .insert_source(Timer::from_duration(timeout), |_, _, app_data| {
+                app_data.lock_data.feedback_timer = None;
+                app_data.lock_data.feedback.clear();
+                app_data.render_all();
+                TimeoutAction::Drop
+            })
+            .expect("Failed to insert the feedback timer!");
+        self.lock_data.feedback_timer = Some(token);
+    }
+
+    /// Unlocks without a password when input arrives within the grace
+    /// period. Returns whether it did.
+    pub fn grace_unlock(&mut self, pointer: bool) -> bool {
+        if !self.lock_data.in_grace(pointer) {
+            return false;
+        }
+        tracing::debug!("Input during the grace period, unlocking");
+        self.unlock();
+        true
+    }
+
+    /// Claims the fingerprint reader if fingerprint unlock is on. Without
+    /// fprintd or a reader only the password works.
+    pub fn start_fingerprint(&mut self) {
+        let Some(finger) = self.lock_data.fingerprint_finger.as_deref() else {
+            return;
+        };
+        match Fprint::start(&self.wayland.loop_handle, &whoami::username(), finger) {
+            // The prompt shows up once the reader is verifying.
+            Ok(fprint) => self.lock_data.fingerprint = Some(fprint),
+            Err(e) => tracing::warn!("Fingerprint unlock is unavailable: {}", e),
+        }
+    }
+
+    /// Acts on a scan from the fingerprint reader. `done` is set once fprintd
+    /// stopped verifying, after which it has to be started again.
+    pub fn fingerprint_scan(&mut self, scan: Scan, done: bool) {
+        if self.lock_data.fingerprint.is_none() {
+            return;
+        }
+        // Someone is at the reader, so the screen should be readable.
+        self.wake();
+        match scan {
+            Scan::Match => {
+                tracing::trace!("Fingerprint matched");
+                self.unlock();
+                return;
+            }
+            Scan::NoMatch => {
+                self.lock_data.feedback = vec!["Fingerprint not recognized".to_string()];
+                self.show_feedback();
+                self.set_auth_state(AuthState::Wrong);
+                self.idle_after_failure();
+            }
+            Scan::Retry(hint) => {
+                self.lock_data.feedback = vec![hint.to_string()];
+                self.show_feedback();
+                self.render_all();
+            }
+            Scan::Failed(status) => {
+                tracing::warn!("Fingerprint reader stopped: {}", status);
+                self.lock_data.fingerprint = None;
+                self.render_all();
+                return;
+            }
+        }
+        if let Some(fprint) = self.lock_data.fingerprint.as_mut().filter(|_| done) {
+            fprint.restart();
+        }
+    }
+
+    /// Redraws every surface whenever a widget's content changes with time,
+    /// such as the clock ticking over.
+    fn schedule_updates(&mut self) {
+        let Some(next) = self.lock_data.layout.update(&self.wayland.loop_handle) else {
+            return;
+        };
+        self.wayland
+            .loop_handle
+            .insert_source(Timer::from_duration(next), |_, _, app_data| {
+                let next = app_data
+                    .lock_data
+                    .layout
+                    .update(&app_data.wayland.loop_handle);
+                app_data.render_all();
+                match next {
+                    Some(next) => TimeoutAction::ToDuration(next),
+                    None => TimeoutAction::Drop,
+                }
+            })
+            .expect("Failed to insert the widget update timer!");
+    }
+
+    pub fn surface_index(&self, wl_surface: &WlSurface) -> Option<usize> {
+        self.lock_data
+            .session_lock_surfaces
+            .iter()
+            .position(|s| s.wl_surface() == wl_surface)
+    }
+}
+
 smithay_client_toolkit::reexports::client::delegate_noop!(AppData: ignore smithay_client_toolkit::reexports::client::protocol::wl_buffer::WlBuffer);
@@ -1 +1,248 @@
+mod paths;
+mod schedule;
 
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveTime};
+use ini::{Ini, Properties};
+use xdg::BaseDirectories;
+
+pub use paths::PathError;
+
+const SYSTEM_CONFIG_DIR: &str = "/etc/xdg/something_lock";
+const CONFIG_FILE_NAME: &str = "conf.ini";
+const DROP_IN_DIR_NAME: &str = "conf.d";
+const DROP_IN_EXTENSION: &str = "ini";
+
+const ADMIN_SECTION: &str = "admin";
+
+/// (section, key) pairs that only the system config may set once `[admin] locked=true`.
+const SECURITY_KEYS: &[(&str, &str)] = &[
+    ("auth", "service"),
+    ("auth", "max_attempts"),
+    ("auth", "lockout"),
+    ("auth", "grace"),
+    ("auth", "grace_manual"),
+    ("auth", "grace_pointer"),
+];
+
+struct Layer {
+    path: PathBuf,
+    system: bool,
+}
+
+/// Merged view of every config file, later layers overriding earlier keys:
+/// `/etc/xdg/something_lock/conf.ini`, the user `conf.ini`, then the system
+/// and user `conf.d/*.ini` drop-ins in file name order. The `[theme.<name>]`
+/// section picked by `[schedule]` is applied on top of all of them.
+pub struct Config {
+    ini: Ini,
+    /// Directory of the file each (section, key) was last set from.
+    origins: HashMap<(String, String), PathBuf>,
+    sources: Vec<PathBuf>,
+    admin_locked: bool,
+}
+
+impl Config {
+    pub fn load(base: &BaseDirectories) -> Self {
+        let system_dir = PathBuf::from(SYSTEM_CONFIG_DIR);
+        let user_dir = base.get_config_home();
+
+        let mut layers = vec![
+            Layer {
+                path: system_dir.join(CONFIG_FILE_NAME),
+                system: true,
+            },
+            Layer {
+                path: user_dir.join(CONFIG_FILE_NAME),
+                system: false,
+            },
+        ];
+        layers.extend(drop_ins(&system_dir.join(DROP_IN_DIR_NAME), true));
+        layers.extend(drop_ins(&user_dir.join(DROP_IN_DIR_NAME), false));
+
+        let parsed: Vec<(Layer, Ini)> = layers
+            .into_iter()
+            .filter(|layer| layer.path.is_file())
+            .filter_map(|layer| match Ini::load_from_file(&layer.path) {
+                Ok(ini) => Some((layer, ini)),
+                Err(e) => {
+                    tracing::error!("Unable to parse config {:?}, skipping: {}", layer.path, e);
+                    None
+                }
+            })
+            .collect();
+
+        // Decided before merging so a system drop-in can lock keys that the
+        // user conf.ini, which is read earlier, tries to set.
+        let admin_locked = parsed
+            .iter()
+            .filter(|(layer, _)| layer.system)
+            .filter_map(|(_, ini)| ini.get_from(Some(ADMIN_SECTION), "locked"))
+            .next_back()
+            .map(|v| v.parse::<bool>().unwrap_or(false))
+            .unwrap_or(false);
+
+        let mut config = Config {
+            ini: Ini::new(),
+            origins: HashMap::new(),
+            sources: Vec::new(),
+            admin_locked,
+        };
+        for (layer, ini) in parsed {
+            config.merge_layer(layer, ini);
+        }
+        config.apply_schedule(Local::now().time());
+        config
+    }
+
+    fn merge_layer(&mut self, layer: Layer, ini: Ini) {
+        tracing::trace!("Config file path: {:?}", layer.path);
+        for (section, properties) in ini.iter() {
+            for (key, value) in properties.iter() {
+                if !layer.system && self.is_protected(section, key) {
+                    tracing::warn!(
+                        "Ignoring [{}] {} from {:?}, it is locked by the system config",
+                        section.unwrap_or_default(),
+                        key,
+                        layer.path
+                    );
+                    continue;
+                }
+                self.ini
+                    .with_section(section)
+                    .set(key.to_string(), value.to_string());
+                if let (Some(section), Some(dir)) = (section, layer.path.parent()) {
+                    self.origins
+                        .insert((section.to_string(), key.to_string()), dir.to_path_buf());
+                }
+            }
+        }
+        self.sources.push(layer.path);
+    }
+
+    /// Overlays the theme scheduled for `now`. Theme keys are written as
+    /// `section.key`, e.g. `main.type=solid` or `solid.red=20`.
+    fn apply_schedule(&mut self, now: NaiveTime) {
+        let Some(theme) = self
+            .section(schedule::SCHEDULE_SECTION)
+            .and_then(|s| schedule::active_theme(s, now))
+        else {
+            return;
+        };
+        let theme_section = format!("{}{}", schedule::THEME_SECTION_PREFIX, theme);
+        let Some(properties) = self.section(&theme_section).cloned() else {
+            tracing::warn!(
+                "Scheduled theme '{}' has no [{}] section",
+                theme,
+                theme_section
+            );
+            return;
+        };
+        tracing::trace!("Applying theme '{}'", theme);
+
+        for (name, value) in properties.iter() {
+            let Some((section, key)) = name.split_once('.') else {
+                tracing::warn!(
+                    "Ignoring [{}] {}, expected section.key",
+                    theme_section,
+                    name
+                );
+                continue;
+            };
+            if self.is_protected(Some(section), key) {
+                tracing::warn!(
+                    "Ignoring [{}] {}, it is locked by the system config",
+                    theme_section,
+                    name
+                );
+                continue;
+            }
+            self.ini
+                .with_section(Some(section))
+                .set(key.to_string(), value.to_string());
+            if let Some(dir) = self
+                .origins
+                .get(&(theme_section.clone(), name.to_string()))
+                .cloned()
+            {
+                self.origins
+                    .insert((section.to_string(), key.to_string()), dir);
+            }
+        }
+    }
+
+    fn is_protected(&self, section: Option<&str>, key: &str) -> bool {
+        match section {
+            Some(ADMIN_SECTION) => true,
+            Some(section) => self.admin_locked && SECURITY_KEYS.contains(&(section, key)),
+            None => false,
+        }
+    }
+
+    /// Whether any config file was found at all.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Properties> {
+        self.ini.section(Some(name))
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.ini.get_from(Some(section), key)
+    }
+
+    /// Reads a path-valued key, expanding `~` and environment variables and
+    /// resolving relative paths against the file the key came from.
+    pub fn get_path(&self, section: &str, key: &str) -> Option<Result<PathBuf, PathError>> {
+        let value = self.get(section, key)?;
+        let relative_to = self
+            .origins
+            .get(&(section.to_string(), key.to_string()))
+            .cloned()
+            .unwrap_or_default();
+        Some(paths::expand(value, &relative_to))
+    }
+}
+
+fn drop_ins(dir: &Path, system: bool) -> Vec<Layer> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path.extension().and_then(|e| e.to_str()) == Some(DROP_IN_EXTENSION)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| Layer { path, system })
+        .collect()
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa` (the `#` is optional) into a color.
+pub fn parse_color(s: &str) -> Option<wgpu::Color> {
+    let hex = s.trim().trim_start_matches('#');
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .map(|c| c as f64 / 255.0)
+    };
+    Some(wgpu::Color {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+        a: if hex.len() == 8 { channel(6)? } else { 1.0 },
+    })
+}
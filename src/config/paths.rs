@@ -0,0 +1,235 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+const USER_DIRS_FILE: &str = "user-dirs.dirs";
+
+#[derive(Debug)]
+pub enum PathErrorKind {
+    NoHome,
+    UndefinedVariable(String),
+    NotFound,
+}
+
+/// A path-valued config key that could not be resolved, keeping both the
+/// value as written and how far expansion got.
+#[derive(Debug)]
+pub struct PathError {
+    pub original: String,
+    pub expanded: PathBuf,
+    pub kind: PathErrorKind,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            PathErrorKind::NoHome => write!(
+                f,
+                "cannot expand '~' in {:?} (expanded to {:?}): HOME is not set",
+                self.original, self.expanded
+            ),
+            PathErrorKind::UndefinedVariable(name) => write!(
+                f,
+                "cannot expand ${} in {:?} (expanded to {:?}): variable is not set",
+                name, self.original, self.expanded
+            ),
+            PathErrorKind::NotFound => write!(
+                f,
+                "{:?} (expanded to {:?}) does not exist",
+                self.original, self.expanded
+            ),
+        }
+    }
+}
+
+/// Expands `~`, `$VAR`/`${VAR}` and, for relative results, joins them onto
+/// `relative_to`. `XDG_*_DIR` variables missing from the environment are
+/// looked up in `user-dirs.dirs` the way xdg-user-dir does.
+pub fn expand(original: &str, relative_to: &Path) -> Result<PathBuf, PathError> {
+    let error = |expanded: &str, kind| PathError {
+        original: original.to_string(),
+        expanded: PathBuf::from(expanded),
+        kind,
+    };
+
+    let mut expanded = String::new();
+    let mut rest = original;
+    if rest == "~" || rest.starts_with("~/") {
+        let home = env::var("HOME").map_err(|_| error(original, PathErrorKind::NoHome))?;
+        expanded.push_str(&home);
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => (braced, after.len()),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            let value = lookup_variable(name).ok_or_else(|| {
+                error(
+                    &format!("{}{}", expanded, rest),
+                    PathErrorKind::UndefinedVariable(name.to_string()),
+                )
+            })?;
+            expanded.push_str(&value);
+        }
+        rest = &after[consumed..];
+    }
+    expanded.push_str(rest);
+
+    let path = PathBuf::from(&expanded);
+    let path = if path.is_relative() {
+        relative_to.join(path)
+    } else {
+        path
+    };
+    if !path.exists() {
+        return Err(PathError {
+            original: original.to_string(),
+            expanded: path,
+            kind: PathErrorKind::NotFound,
+        });
+    }
+    Ok(path)
+}
+
+fn lookup_variable(name: &str) -> Option<String> {
+    if let Ok(value) = env::var(name) {
+        return Some(value);
+    }
+    if name.starts_with("XDG_") && name.ends_with("_DIR") {
+        return user_dir(name);
+    }
+    None
+}
+
+fn user_dir(name: &str) -> Option<String> {
+    let home = env::var("HOME").ok()?;
+    let config_home = env::var("XDG_CONFIG_HOME").unwrap_or(format!("{}/.config", home));
+    let contents = fs::read_to_string(Path::new(&config_home).join(USER_DIRS_FILE)).ok()?;
+    contents
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.trim_matches('"').replace("$HOME", &home))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn expands_home_and_user_dirs() {
+        let home = TempDir::new();
+        let wallpaper = home.write("Pictures/wall.png", "");
+        home.write(
+            ".config/user-dirs.dirs",
+            "# written by xdg-user-dirs-update\nXDG_PICTURES_DIR=\"$HOME/Pictures\"\n",
+        );
+        // The only test touching HOME and the XDG variables.
+        env::set_var("HOME", home.path());
+        env::remove_var("XDG_CONFIG_HOME");
+        env::remove_var("XDG_PICTURES_DIR");
+        let elsewhere = Path::new("/nonexistent");
+
+        assert_eq!(expand("~", elsewhere).unwrap(), home.path());
+        assert_eq!(expand("~/Pictures/wall.png", elsewhere).unwrap(), wallpaper);
+        assert_eq!(
+            expand("$XDG_PICTURES_DIR/wall.png", elsewhere).unwrap(),
+            wallpaper
+        );
+        assert_eq!(
+            expand("${XDG_PICTURES_DIR}/wall.png", elsewhere).unwrap(),
+            wallpaper
+        );
+        // Only a leading `~/` is the home directory.
+        assert!(expand("~other/wall.png", elsewhere).is_err());
+    }
+
+    #[test]
+    fn expands_variables() {
+        let dir = TempDir::new();
+        let image = dir.write("a.png", "");
+        env::set_var("SOMETHING_LOCK_TEST_DIR", dir.path());
+        let elsewhere = Path::new("/nonexistent");
+
+        assert_eq!(
+            expand("$SOMETHING_LOCK_TEST_DIR/a.png", elsewhere).unwrap(),
+            image
+        );
+        assert_eq!(
+            expand("${SOMETHING_LOCK_TEST_DIR}/a.png", elsewhere).unwrap(),
+            image
+        );
+        // An unterminated `${` takes the rest as the name.
+        assert_eq!(
+            expand("${SOMETHING_LOCK_TEST_DIR", elsewhere).unwrap(),
+            dir.path()
+        );
+        let e = expand("${SOMETHING_LOCK_TEST_DIR/a.png", elsewhere).unwrap_err();
+        assert!(
+            matches!(e.kind, PathErrorKind::UndefinedVariable(ref name) if name == "SOMETHING_LOCK_TEST_DIR/a.png"),
+            "{:?}",
+            e
+        );
+    }
+
+    #[test]
+    fn keeps_lone_dollar_signs() {
+        let dir = TempDir::new();
+        let image = dir.write("$-a.png", "");
+        assert_eq!(expand("$-a.png", dir.path()).unwrap(), image);
+    }
+
+    #[test]
+    fn joins_relative_paths_onto_the_config() {
+        let dir = TempDir::new();
+        let image = dir.write("themes/a.png", "");
+        assert_eq!(expand("themes/a.png", dir.path()).unwrap(), image);
+        assert_eq!(
+            expand(image.to_str().unwrap(), Path::new("/nonexistent")).unwrap(),
+            image
+        );
+    }
+
+    #[test]
+    fn errors_show_both_paths() {
+        let dir = TempDir::new();
+        let e = expand("missing/a.png", dir.path()).unwrap_err();
+        assert!(matches!(e.kind, PathErrorKind::NotFound));
+        assert_eq!(e.expanded, dir.path().join("missing/a.png"));
+        let message = e.to_string();
+        assert!(message.contains("\"missing/a.png\""), "{}", message);
+        assert!(
+            message.contains(&format!("{:?}", dir.path().join("missing/a.png"))),
+            "{}",
+            message
+        );
+
+        let e = expand("themes/$SOMETHING_LOCK_UNSET/a.png", dir.path()).unwrap_err();
+        assert!(
+            matches!(e.kind, PathErrorKind::UndefinedVariable(ref name) if name == "SOMETHING_LOCK_UNSET")
+        );
+        let message = e.to_string();
+        assert!(message.contains("$SOMETHING_LOCK_UNSET"), "{}", message);
+        assert!(
+            message.contains("\"themes/$SOMETHING_LOCK_UNSET/a.png\""),
+            "{}",
+            message
+        );
+    }
+}
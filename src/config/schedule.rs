@@ -0,0 +1,113 @@
+use chrono::NaiveTime;
+use ini::Properties;
+
+pub const SCHEDULE_SECTION: &str = "schedule";
+pub const THEME_SECTION_PREFIX: &str = "theme.";
+
+/// Parses one `HH:MM=theme` schedule line. The INI parser also splits on
+/// ':', so `07:00=day` arrives as key `07` and value `00=day` and is put
+/// back together here; `07\:00=day` arrives whole.
+fn parse_entry(key: &str, value: &str) -> Option<(NaiveTime, String)> {
+    let (time, theme) = if key.contains(':') {
+        (key.to_string(), value)
+    } else {
+        let (minutes, theme) = value.split_once('=')?;
+        (format!("{}:{}", key, minutes), theme)
+    };
+    let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").ok()?;
+    Some((time, theme.trim().to_string()))
+}
+
+/// Name of the theme whose start time most recently passed at `now`. Before
+/// the earliest entry, the last entry of the day is still in effect.
+pub fn active_theme(schedule: &Properties, now: NaiveTime) -> Option<String> {
+    let mut entries: Vec<(NaiveTime, String)> = schedule
+        .iter()
+        .filter_map(|(key, value)| {
+            let entry = parse_entry(key, value);
+            if entry.is_none() {
+                tracing::warn!(
+                    "Ignoring schedule entry '{}={}', expected HH:MM=theme",
+                    key,
+                    value
+                );
+            }
+            entry
+        })
+        .collect();
+    entries.sort_by_key(|(time, _)| *time);
+
+    entries
+        .iter()
+        .rev()
+        .find(|(time, _)| *time <= now)
+        .or(entries.last())
+        .map(|(_, theme)| theme.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use ini::Ini;
+
+    use super::*;
+
+    fn schedule(entries: &str) -> Properties {
+        let ini = Ini::load_from_str(&format!("[{}]\n{}", SCHEDULE_SECTION, entries)).unwrap();
+        ini.section(Some(SCHEDULE_SECTION)).unwrap().clone()
+    }
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn joins_times_split_by_the_ini_parser() {
+        let entries = schedule("07:00=day\n19\\:30=night\n");
+        let parsed: Vec<_> = entries
+            .iter()
+            .map(|(key, value)| parse_entry(key, value))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                Some((at("07:00"), "day".to_string())),
+                Some((at("19:30"), "night".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_the_latest_entry_at_or_before_now() {
+        let entries = schedule("07:00=day\n12:00=noon\n19:30=night\n");
+        assert_eq!(active_theme(&entries, at("07:00")).as_deref(), Some("day"));
+        assert_eq!(active_theme(&entries, at("11:59")).as_deref(), Some("day"));
+        assert_eq!(active_theme(&entries, at("12:00")).as_deref(), Some("noon"));
+        assert_eq!(
+            active_theme(&entries, at("23:59")).as_deref(),
+            Some("night")
+        );
+    }
+
+    #[test]
+    fn wraps_around_to_the_last_entry_of_the_day() {
+        // Written out of order on purpose.
+        let entries = schedule("19:30=night\n07:00=day\n");
+        assert_eq!(
+            active_theme(&entries, at("06:59")).as_deref(),
+            Some("night")
+        );
+        assert_eq!(
+            active_theme(&entries, at("00:00")).as_deref(),
+            Some("night")
+        );
+    }
+
+    #[test]
+    fn skips_bad_times() {
+        let entries = schedule("25:00=never\nnoon=never\n07:00=day\n");
+        assert_eq!(parse_entry("25", "00=never"), None);
+        assert_eq!(parse_entry("noon", "never"), None);
+        assert_eq!(active_theme(&entries, at("06:00")).as_deref(), Some("day"));
+        assert_eq!(active_theme(&schedule("noon=never\n"), at("12:00")), None);
+    }
+}
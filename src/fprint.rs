@@ -0,0 +1,396 @@
+use std::{
+    os::fd::{AsFd, BorrowedFd},
+    rc::Rc,
+    time::Duration,
+};
+
+use dbus::{
+    channel::{BusType, Channel},
+    message::{MatchRule, MessageType},
+    Message, Path,
+};
+use smithay_client_toolkit::reexports::calloop::{
+    generic::Generic, Interest, LoopHandle, Mode, PostAction,
+};
+
+use crate::app::AppData;
+
+const SERVICE: &str = "net.reactivated.Fprint";
+const MANAGER_PATH: &str = "/net/reactivated/Fprint/Manager";
+const MANAGER_INTERFACE: &str = "net.reactivated.Fprint.Manager";
+const DEVICE_INTERFACE: &str = "net.reactivated.Fprint.Device";
+const BUS_SERVICE: &str = "org.freedesktop.DBus";
+const BUS_PATH: &str = "/org/freedesktop/DBus";
+
+/// What the reader made of a scan, from fprintd's `VerifyStatus`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scan {
+    Match,
+    NoMatch,
+    /// The scan was unusable; the text says what to do about it.
+    Retry(&'static str),
+    /// The reader is gone or failed, and verifying has stopped.
+    Failed(String),
+}
+
+impl Scan {
+    fn from_status(result: &str) -> Self {
+        match result {
+            "verify-match" => Scan::Match,
+            "verify-no-match" => Scan::NoMatch,
+            "verify-retry-scan" => Scan::Retry("Scan your finger again"),
+            "verify-swipe-too-short" => Scan::Retry("Swipe was too short, try again"),
+            "verify-finger-not-centered" => Scan::Retry("Center your finger on the reader"),
+            "verify-remove-and-retry" => Scan::Retry("Remove your finger and try again"),
+            other => Scan::Failed(other.to_string()),
+        }
+    }
+}
+
+/// How far getting the reader ready has come. Each step waits for the
+/// reply to the call that started it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Device,
+    Claim,
+    Start,
+    Verifying,
+}
+
+impl Stage {
+    fn failure(self) -> &'static str {
+        match self {
+            Stage::Device => "no fingerprint reader",
+            Stage::Claim => "unable to claim the reader",
+            Stage::Start | Stage::Verifying => "unable to start verifying",
+        }
+    }
+}
+
+/// The system bus fd, for the event loop to watch. Holding the channel
+/// keeps the fd open for as long as the event source.
+struct BusFd(Rc<Channel>);
+
+impl AsFd for BusFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0.watch().fd) }
+    }
+}
+
+/// Fingerprint verification through fprintd, running next to password
+/// entry. Nothing here waits on fprintd: calls are sent and their replies
+/// picked up by [`Fprint::dispatch`]. Once claimed, the reader stays
+/// claimed until this is dropped.
+pub struct Fprint {
+    bus: Rc<Channel>,
+    username: String,
+    finger: String,
+    device: Option<Path<'static>>,
+    stage: Stage,
+    /// Serial of the call whose reply is awaited.
+    pending: Option<u32>,
+}
+
+impl Fprint {
+    /// Starts claiming the default reader for `username` and verifying
+    /// `finger`, or any enrolled one with `any`. Each scan is handed to
+    /// [`AppData::fingerprint_scan`] on the event loop, along with whether
+    /// verifying has finished.
+    pub fn start(
+        events: &LoopHandle<'static, AppData>,
+        username: &str,
+        finger: &str,
+    ) -> Result<Self, dbus::Error> {
+        let fprint = Self::connect(username, finger)?;
+        events
+            .insert_source(
+                Generic::new(BusFd(fprint.bus.clone()), Interest::READ, Mode::Level),
+                |_, _, app_data| {
+                    // Gone after failing or unlocking, which closes the
+                    // connection along with the source.
+                    let Some(fprint) = app_data.lock_data.fingerprint.as_mut() else {
+                        return Ok(PostAction::Remove);
+                    };
+                    let verifying = fprint.is_verifying();
+                    let scans = fprint.dispatch();
+                    let started = !verifying && fprint.is_verifying();
+                    for (scan, done) in scans {
+                        app_data.fingerprint_scan(scan, done);
+                    }
+                    if started {
+                        app_data.render_all();
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .expect("Failed to insert the system bus source!");
+        Ok(fprint)
+    }
+
+    /// Connects to the system bus and asks fprintd for the default reader.
+    fn connect(username: &str, finger: &str) -> Result<Self, dbus::Error> {
+        let mut channel = Channel::get_private(BusType::System)?;
+        channel.set_watch_enabled(true);
+        let mut fprint = Self {
+            bus: Rc::new(channel),
+            username: username.to_string(),
+            finger: finger.to_string(),
+            device: None,
+            stage: Stage::Device,
+            pending: None,
+        };
+        let call =
+            Message::new_method_call(SERVICE, MANAGER_PATH, MANAGER_INTERFACE, "GetDefaultDevice")
+                .unwrap();
+        fprint.call(call)?;
+        Ok(fprint)
+    }
+
+    /// Whether fprintd is verifying, so a finger on the reader is read.
+    pub fn is_verifying(&self) -> bool {
+        self.stage == Stage::Verifying
+    }
+
+    /// Reads whatever the bus has for us without waiting, moves the set up
+    /// along, and returns the scans that came in with whether verifying
+    /// finished after each.
+    pub fn dispatch(&mut self) -> Vec<(Scan, bool)> {
+        let mut scans = Vec::new();
+        if self.bus.read_write(Some(Duration::ZERO)).is_err() {
+            scans.push((Scan::Failed("lost the system bus".to_string()), true));
+            return scans;
+        }
+        while let Some(msg) = self.bus.pop_message() {
+            match msg.msg_type() {
+                MessageType::Signal if self.is_status(&msg) => {
+                    if let Ok((result, done)) = msg.read2::<String, bool>() {
+                        scans.push((Scan::from_status(&result), done));
+                    }
+                }
+                MessageType::MethodReturn | MessageType::Error
+                    if msg.get_reply_serial() == self.pending =>
+                {
+                    self.pending = None;
+                    let stage = self.stage;
+                    if let Err(e) = self.advance(msg) {
+                        scans.push((Scan::Failed(format!("{}: {}", stage.failure(), e)), true));
+                    }
+                }
+                _ => {}
+            }
+        }
+        scans
+    }
+
+    fn is_status(&self, msg: &Message) -> bool {
+        msg.path().as_deref() == self.device.as_deref()
+            && msg.interface().as_deref() == Some(DEVICE_INTERFACE)
+            && msg.member().as_deref() == Some("VerifyStatus")
+    }
+
+    /// Takes the reply to the current step and sends the next call.
+    fn advance(&mut self, mut reply: Message) -> Result<(), dbus::Error> {
+        reply.as_result()?;
+        match self.stage {
+            Stage::Device => {
+                let device: Path<'static> = reply.read1()?;
+                // The bus only passes signals on once asked to.
+                let mut rule = MatchRule::new_signal(DEVICE_INTERFACE, "VerifyStatus");
+                rule.path = Some(device.clone());
+                let add_match =
+                    Message::new_method_call(BUS_SERVICE, BUS_PATH, BUS_SERVICE, "AddMatch")
+                        .unwrap()
+                        .append1(rule.match_str());
+                self.send(add_match)?;
+                self.device = Some(device);
+                self.stage = Stage::Claim;
+                let claim = self.device_call("Claim").append1(&self.username);
+                self.call(claim)
+            }
+            Stage::Claim => {
+                self.stage = Stage::Start;
+                let start = self.device_call("VerifyStart").append1(&self.finger);
+                self.call(start)
+            }
+            Stage::Start => {
+                self.stage = Stage::Verifying;
+                Ok(())
+            }
+            Stage::Verifying => Ok(()),
+        }
+    }
+
+    /// Verifies again after fprintd finished without a match.
+    pub fn restart(&mut self) {
+        if self.stage != Stage::Verifying {
+            return;
+        }
+        let start = self.device_call("VerifyStart").append1(&self.finger);
+        let sent = self
+            .send(self.device_call("VerifyStop"))
+            .and_then(|_| self.call(start));
+        if let Err(e) = sent {
+            tracing::warn!("Unable to restart fingerprint verification: {}", e);
+        }
+    }
+
+    fn device_call(&self, method: &str) -> Message {
+        let device = self.device.as_ref().expect("No fingerprint reader yet");
+        Message::new_method_call(SERVICE, device, DEVICE_INTERFACE, method).unwrap()
+    }
+
+    fn send(&self, msg: Message) -> Result<u32, dbus::Error> {
+        let serial = self
+            .bus
+            .send(msg)
+            .map_err(|_| dbus::Error::new_failed("the system bus is gone"))?;
+        self.bus.flush();
+        Ok(serial)
+    }
+
+    /// Sends `msg` as the call whose reply moves things along.
+    fn call(&mut self, msg: Message) -> Result<(), dbus::Error> {
+        self.pending = Some(self.send(msg)?);
+        Ok(())
+    }
+}
+
+impl Drop for Fprint {
+    fn drop(&mut self) {
+        if !matches!(self.stage, Stage::Start | Stage::Verifying) {
+            return;
+        }
+        // Stopping fails when nothing is being verified, which is fine.
+        let _ = self.send(self.device_call("VerifyStop"));
+        if let Err(e) = self.send(self.device_call("Release")) {
+            tracing::warn!("Unable to release the fingerprint reader: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use dbus::{
+        blocking::LocalConnection,
+        channel::{Channel, MatchingReceiver},
+        message::MatchRule,
+        Message, Path,
+    };
+
+    use super::*;
+    use crate::testing::private_bus;
+
+    const DEVICE_PATH: &str = "/net/reactivated/Fprint/Device/0";
+
+    /// Answers like fprintd with one reader, reporting `results` in turn
+    /// for each `VerifyStart`. Says when the reader is released.
+    fn fake_fprintd(address: String, results: Vec<&'static str>) -> mpsc::Receiver<&'static str> {
+        let (events, received) = mpsc::channel();
+        thread::spawn(move || {
+            let mut channel = Channel::open_private(&address).unwrap();
+            channel.register().unwrap();
+            let conn = LocalConnection::from(channel);
+            conn.request_name(SERVICE, false, true, true).unwrap();
+            events.send("ready").unwrap();
+            let mut results = results.into_iter();
+            let sender = events.clone();
+            conn.start_receive(
+                MatchRule::new_method_call(),
+                Box::new(move |call: Message, conn: &LocalConnection| {
+                    let reply = match call.member().as_deref() {
+                        Some("GetDefaultDevice") => {
+                            call.method_return().append1(Path::from(DEVICE_PATH))
+                        }
+                        Some("Claim") if call.read1::<&str>() == Ok("user") => call.method_return(),
+                        Some("Claim") => call.error(
+                            &"net.reactivated.Fprint.Error.PermissionDenied".into(),
+                            c"Not the locked user",
+                        ),
+                        Some("VerifyStart") => {
+                            let _ = conn.channel().send(call.method_return());
+                            let status =
+                                Message::new_signal(DEVICE_PATH, DEVICE_INTERFACE, "VerifyStatus")
+                                    .unwrap()
+                                    .append2(
+                                        results.next().unwrap_or("verify-unknown-error"),
+                                        true,
+                                    );
+                            let _ = conn.channel().send(status);
+                            return true;
+                        }
+                        Some("Release") => {
+                            let _ = sender.send("released");
+                            call.method_return()
+                        }
+                        _ => call.method_return(),
+                    };
+                    let _ = conn.channel().send(reply);
+                    true
+                }),
+            );
+            while conn.process(Duration::from_millis(50)).is_ok() {}
+        });
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)), Ok("ready"));
+        received
+    }
+
+    fn next_scan(fprint: &mut Fprint) -> (Scan, bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(scan) = fprint.dispatch().into_iter().next() {
+                return scan;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("No scan from the fake fprintd");
+    }
+
+    #[test]
+    fn reads_statuses() {
+        assert_eq!(Scan::from_status("verify-match"), Scan::Match);
+        assert_eq!(Scan::from_status("verify-no-match"), Scan::NoMatch);
+        assert!(matches!(
+            Scan::from_status("verify-finger-not-centered"),
+            Scan::Retry(_)
+        ));
+        assert_eq!(
+            Scan::from_status("verify-disconnected"),
+            Scan::Failed("verify-disconnected".to_string())
+        );
+    }
+
+    #[test]
+    fn verifies_through_fprintd() {
+        let Some((_bus, address)) = private_bus() else {
+            eprintln!("Skipping, dbus-daemon is not available");
+            return;
+        };
+        let events = fake_fprintd(address.clone(), vec!["verify-no-match", "verify-match"]);
+        // The only test touching the system bus address.
+        std::env::set_var("DBUS_SYSTEM_BUS_ADDRESS", &address);
+
+        let mut fprint = Fprint::connect("user", "any").unwrap();
+        assert_eq!(next_scan(&mut fprint), (Scan::NoMatch, true));
+        assert!(fprint.is_verifying());
+        fprint.restart();
+        assert_eq!(next_scan(&mut fprint), (Scan::Match, true));
+
+        drop(fprint);
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)), Ok("released"));
+
+        let mut fprint = Fprint::connect("someone else", "any").unwrap();
+        let (scan, done) = next_scan(&mut fprint);
+        assert!(
+            matches!(scan, Scan::Failed(ref e) if e.contains("claim")),
+            "{:?}",
+            scan
+        );
+        assert!(done && !fprint.is_verifying());
+    }
+}
@@ -247,49 +247,12 @@ impl Graphics {
         surface.set_texture(texture);
     }
 
-    pub fn render_texture_for_image(
-        &self,
-        surface: &LockSurfaceWrapper,
-    ) -> Result<(), wgpu::SurfaceError> {
-        let output = surface.get_surface().get_current_texture().unwrap();
-        let device = self.device.as_ref().unwrap();
-        let queue = self.queue.as_ref().unwrap();
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_pipeline(surface.pipeline());
-            render_pass.set_bind_group(0, surface.bind_group(), &[]);
-            //            render_pass.set_bind_group(1, self.blur_bind_group.as_ref().unwrap(), &[]);
-            render_pass.set_vertex_buffer(0, surface.vertex_buffer().slice(..));
-            render_pass
-                .set_index_buffer(surface.index_buffer().slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
-        }
-
-        queue.submit(std::iter::once(encoder.finish()));
-
-        output.present();
-
-        Ok(())
+    pub fn draw_image(&self, render_pass: &mut wgpu::RenderPass<'_>, surface: &LockSurfaceWrapper) {
+        render_pass.set_pipeline(surface.pipeline());
+        render_pass.set_bind_group(0, surface.bind_group(), &[]);
+        //            render_pass.set_bind_group(1, self.blur_bind_group.as_ref().unwrap(), &[]);
+        render_pass.set_vertex_buffer(0, surface.vertex_buffer().slice(..));
+        render_pass.set_index_buffer(surface.index_buffer().slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
     }
 }
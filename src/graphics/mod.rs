@@ -4,9 +4,18 @@ use wgpu::{
 };
 
 mod image;
+pub mod overlay;
 mod shader;
+pub mod shapes;
+pub mod sprites;
 pub mod surface;
+pub mod text;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::shapes::{Shape, ShapeRenderer};
+use crate::graphics::sprites::SpriteRenderer;
 use crate::graphics::surface::LockSurfaceWrapper;
+use crate::graphics::text::TextRenderer;
+use crate::media::Media;
 
 pub struct Graphics {
     pub instance: Instance,
@@ -18,6 +27,9 @@ pub struct Graphics {
     texture_bind_group: Option<BindGroup>,
     vertex_buffer: Option<Buffer>,
     index_buffer: Option<Buffer>,
+    text: Option<TextRenderer>,
+    shapes: Option<ShapeRenderer>,
+    sprites: Option<SpriteRenderer>,
 }
 
 impl Graphics {
@@ -56,10 +68,21 @@ impl Graphics {
             texture_bind_group: None,
             vertex_buffer: None,
             index_buffer: None,
+            text: None,
+            shapes: None,
+            sprites: None,
         }
     }
 
+    pub fn text(&mut self) -> &mut TextRenderer {
+        self.text.as_mut().unwrap()
+    }
+
     pub async fn init(&mut self, compatible_surface: Option<&Surface<'_>>) {
+        // Every lock surface shares one device, so only the first one sets it up.
+        if self.device.is_some() {
+            return;
+        }
         let adapter = self
             .instance
             .request_adapter(&RequestAdapterOptions {
@@ -83,34 +106,82 @@ impl Graphics {
             .await
             .expect("Unable to find a suitable GPU adapter");
 
+        self.text = Some(TextRenderer::new(&device));
+        self.shapes = Some(ShapeRenderer::new(&device));
+        self.sprites = Some(SpriteRenderer::new(&device));
         self.adapter = Some(adapter);
         self.device = Some(device);
         self.queue = Some(queue);
     }
 
-    pub fn render_color(
-        &self,
+    /// Draws `media` and then `overlay` on top of it, and presents the frame.
+    pub fn render(
+        &mut self,
         surface: &LockSurfaceWrapper,
-        color: wgpu::Color,
+        media: &Media,
+        overlay: &Overlay,
     ) -> Result<(), wgpu::SurfaceError> {
-        let output = surface.get_surface().get_current_texture().unwrap();
+        let output = surface.get_surface().get_current_texture()?;
         let device = self.device.as_ref().unwrap();
         let queue = self.queue.as_ref().unwrap();
+        let text = self.text.as_mut().unwrap();
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let shape_batch =
+            self.shapes
+                .as_ref()
+                .unwrap()
+                .prepare(device, &overlay.shapes, surface.size());
+        let sprite_batch =
+            self.sprites
+                .as_mut()
+                .unwrap()
+                .prepare(device, queue, &overlay.sprites, surface.size());
+        // Drawn last, over everything else.
+        let dim_batch = (overlay.dim > 0.0)
+            .then(|| {
+                let (width, height) = surface.size();
+                let dim = Shape::Rect {
+                    position: (0.0, 0.0),
+                    size: (width as f32, height as f32),
+                    corner_radius: 0.0,
+                    color: wgpu::Color {
+                        a: overlay.dim.min(1.0) as f64,
+                        ..wgpu::Color::BLACK
+                    },
+                };
+                self.shapes
+                    .as_ref()
+                    .unwrap()
+                    .prepare(device, &[dim], surface.size())
+            })
+            .flatten();
+        let text_batch = text.prepare(
+            device,
+            queue,
+            &overlay.texts,
+            surface.scale_factor() as f32,
+            surface.size(),
+        );
+
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
 
         {
-            let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            let clear_color = match media {
+                Media::Solid(color) => *color,
+                _ => wgpu::Color::BLACK,
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(color),
+                        load: wgpu::LoadOp::Clear(clear_color),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -118,9 +189,48 @@ impl Graphics {
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
+
+            match media {
+                Media::Image(_) => self.draw_image(&mut render_pass, surface),
+                Media::Shader(_) => self.draw_shader(&mut render_pass),
+                _ => {}
+            }
+
+            if let Some(batch) = &shape_batch {
+                self.shapes.as_mut().unwrap().draw(
+                    self.device.as_ref().unwrap(),
+                    &mut render_pass,
+                    surface.format(),
+                    batch,
+                );
+            }
+            if let Some(batch) = &sprite_batch {
+                self.sprites.as_mut().unwrap().draw(
+                    self.device.as_ref().unwrap(),
+                    &mut render_pass,
+                    surface.format(),
+                    batch,
+                );
+            }
+            if let Some(batch) = &text_batch {
+                self.text.as_mut().unwrap().draw(
+                    self.device.as_ref().unwrap(),
+                    &mut render_pass,
+                    surface.format(),
+                    batch,
+                );
+            }
+            if let Some(batch) = &dim_batch {
+                self.shapes.as_mut().unwrap().draw(
+                    self.device.as_ref().unwrap(),
+                    &mut render_pass,
+                    surface.format(),
+                    batch,
+                );
+            }
         }
 
-        queue.submit(std::iter::once(encoder.finish()));
+        self.queue().submit(std::iter::once(encoder.finish()));
 
         output.present();
 
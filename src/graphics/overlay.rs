@@ -0,0 +1,34 @@
+use super::{shapes::Shape, sprites::Sprite, text::TextSection};
+use crate::widgets::Action;
+
+/// A box on the surface that runs `action` when clicked, in physical
+/// pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct Target {
+    pub position: (f32, f32),
+    pub size: (f32, f32),
+    pub action: Action,
+}
+
+impl Target {
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        point.0 >= self.position.0
+            && point.1 >= self.position.1
+            && point.0 < self.position.0 + self.size.0
+            && point.1 < self.position.1 + self.size.1
+    }
+}
+
+/// Everything drawn on top of a surface's `Media` in one frame. Shapes go
+/// first, then images, so text always ends up on top. `targets` aren't
+/// drawn, they say what a click does.
+#[derive(Default)]
+pub struct Overlay {
+    pub shapes: Vec<Shape>,
+    pub sprites: Vec<Sprite>,
+    pub texts: Vec<TextSection>,
+    pub targets: Vec<Target>,
+    /// Darkens the whole frame, text included, from 0 (untouched) to 1
+    /// (black).
+    pub dim: f32,
+}
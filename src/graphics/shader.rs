@@ -116,48 +116,13 @@ impl Graphics {
         self.pipeline = Some(render_pipeline);
     }
 
-    pub fn render_texture_for_shader(
-        &self,
-        surface: &LockSurfaceWrapper,
-    ) -> Result<(), wgpu::SurfaceError> {
-        let output = surface.get_surface().get_current_texture().unwrap();
-        let device = self.device();
-        let queue = self.queue();
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().unwrap().slice(..));
-            render_pass.set_index_buffer(
-                self.index_buffer.as_ref().unwrap().slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
-        }
-
-        queue.submit(std::iter::once(encoder.finish()));
-
-        output.present();
-
-        Ok(())
+    pub fn draw_shader(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.as_ref().unwrap().slice(..));
+        render_pass.set_index_buffer(
+            self.index_buffer.as_ref().unwrap().slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+        render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
     }
 }
@@ -0,0 +1,63 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) local: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) shape: vec4<f32>,
+    @location(4) arc: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) shape: vec4<f32>,
+    @location(3) arc: vec2<f32>,
+};
+
+const TAU: f32 = 6.28318530718;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.local = in.local;
+    out.color = in.color;
+    out.shape = in.shape;
+    out.arc = in.arc;
+    return out;
+}
+
+// shape.x selects the primitive:
+//   0: ring, shape.y = inner radius, shape.z = outer radius, arc = start/end
+//      angle in radians clockwise from the top
+//   1: rounded rectangle, shape.yz = half size, shape.w = corner radius
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var coverage: f32;
+    if in.shape.x < 0.5 {
+        let d = length(in.local);
+        coverage = clamp(in.shape.z - d + 0.5, 0.0, 1.0) * clamp(d - in.shape.y + 0.5, 0.0, 1.0);
+        if in.arc.y - in.arc.x < TAU {
+            var angle = atan2(in.local.x, -in.local.y);
+            if angle < 0.0 {
+                angle = angle + TAU;
+            }
+            var start = in.arc.x % TAU;
+            if start < 0.0 {
+                start = start + TAU;
+            }
+            var delta = angle - start;
+            if delta < 0.0 {
+                delta = delta + TAU;
+            }
+            if delta > in.arc.y - in.arc.x {
+                coverage = 0.0;
+            }
+        }
+    } else {
+        let q = abs(in.local) - in.shape.yz + vec2<f32>(in.shape.w);
+        let d = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - in.shape.w;
+        coverage = clamp(0.5 - d, 0.0, 1.0);
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,31 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) opacity: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) opacity: f32,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.opacity = in.opacity;
+    return out;
+}
+
+@group(0) @binding(0)
+var sprite: texture_2d<f32>;
+@group(0) @binding(1)
+var sprite_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(sprite, sprite_sampler, in.tex_coords);
+    return vec4<f32>(color.rgb, color.a * in.opacity);
+}
@@ -0,0 +1,264 @@
+use std::{collections::HashMap, f32::consts::TAU};
+
+use wgpu::util::DeviceExt;
+
+const SHAPE_RING: f32 = 0.0;
+const SHAPE_RECT: f32 = 1.0;
+/// Extra pixels around every quad so anti-aliased edges are not clipped.
+const QUAD_MARGIN: f32 = 1.0;
+
+/// A filled primitive drawn in physical pixels. Angles are in radians,
+/// clockwise from the top.
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Ring {
+        center: (f32, f32),
+        inner_radius: f32,
+        outer_radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        color: wgpu::Color,
+    },
+    Rect {
+        position: (f32, f32),
+        size: (f32, f32),
+        corner_radius: f32,
+        color: wgpu::Color,
+    },
+}
+
+impl Shape {
+    /// A filled circle.
+    pub fn circle(center: (f32, f32), radius: f32, color: wgpu::Color) -> Self {
+        Shape::Ring {
+            center,
+            inner_radius: 0.0,
+            outer_radius: radius,
+            start_angle: 0.0,
+            end_angle: TAU,
+            color,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    local: [f32; 2],
+    color: [f32; 4],
+    shape: [f32; 4],
+    arc: [f32; 2],
+}
+
+impl Vertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const F32X2: wgpu::BufferAddress = std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress;
+        const F32X4: wgpu::BufferAddress = std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress;
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: F32X2,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: F32X2 * 2,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: F32X2 * 2 + F32X4,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: F32X2 * 2 + F32X4 * 2,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+            ],
+        }
+    }
+}
+
+/// Vertices for one frame's shapes, ready to be drawn into a render pass.
+pub struct ShapeBatch {
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+/// Draws anti-aliased rings, circles and rounded rectangles from signed
+/// distance functions, one quad per shape.
+pub struct ShapeRenderer {
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl ShapeRenderer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shape Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shape Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "shaders/shape.wgsl"
+            ))),
+        });
+        Self {
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    fn vertices(shape: &Shape, target_size: (u32, u32)) -> [Vertex; 6] {
+        let (width, height) = (target_size.0 as f32, target_size.1 as f32);
+        let to_ndc = |x: f32, y: f32| [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0];
+
+        let (center, half, color, params, arc) = match *shape {
+            Shape::Ring {
+                center,
+                inner_radius,
+                outer_radius,
+                start_angle,
+                end_angle,
+                color,
+            } => (
+                center,
+                (outer_radius, outer_radius),
+                color,
+                [SHAPE_RING, inner_radius, outer_radius, 0.0],
+                [start_angle, end_angle],
+            ),
+            Shape::Rect {
+                position,
+                size,
+                corner_radius,
+                color,
+            } => {
+                let half = (size.0 / 2.0, size.1 / 2.0);
+                (
+                    (position.0 + half.0, position.1 + half.1),
+                    half,
+                    color,
+                    [
+                        SHAPE_RECT,
+                        half.0,
+                        half.1,
+                        corner_radius.min(half.0).min(half.1),
+                    ],
+                    [0.0, TAU],
+                )
+            }
+        };
+        let color = [
+            color.r as f32,
+            color.g as f32,
+            color.b as f32,
+            color.a as f32,
+        ];
+        let (hx, hy) = (half.0 + QUAD_MARGIN, half.1 + QUAD_MARGIN);
+        let corner = |dx: f32, dy: f32| Vertex {
+            position: to_ndc(center.0 + dx, center.1 + dy),
+            local: [dx, dy],
+            color,
+            shape: params,
+            arc,
+        };
+        let top_left = corner(-hx, -hy);
+        let top_right = corner(hx, -hy);
+        let bottom_left = corner(-hx, hy);
+        let bottom_right = corner(hx, hy);
+        [
+            top_left,
+            bottom_left,
+            bottom_right,
+            bottom_right,
+            top_right,
+            top_left,
+        ]
+    }
+
+    /// Builds the vertices for `shapes`, or `None` when there is nothing to draw.
+    pub fn prepare(
+        &self,
+        device: &wgpu::Device,
+        shapes: &[Shape],
+        target_size: (u32, u32),
+    ) -> Option<ShapeBatch> {
+        if shapes.is_empty() {
+            return None;
+        }
+        let vertices: Vec<Vertex> = shapes
+            .iter()
+            .flat_map(|shape| Self::vertices(shape, target_size))
+            .collect();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shape Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Some(ShapeBatch {
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+        })
+    }
+
+    fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> &wgpu::RenderPipeline {
+        self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Shape Pipeline"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_main",
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        })
+    }
+
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        format: wgpu::TextureFormat,
+        batch: &ShapeBatch,
+    ) {
+        render_pass.set_pipeline(self.pipeline(device, format));
+        render_pass.set_vertex_buffer(0, batch.vertex_buffer.slice(..));
+        render_pass.draw(0..batch.vertex_count, 0..1);
+    }
+}
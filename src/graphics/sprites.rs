@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use image::{
+    imageops::{resize, FilterType},
+    RgbaImage,
+};
+use wgpu::util::DeviceExt;
+
+/// An image drawn at `position` with `size`, both in physical pixels.
+/// Textures are uploaded the first time an image is seen and dropped once
+/// every `Rc` to it is gone.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub image: Rc<RgbaImage>,
+    pub position: (f32, f32),
+    pub size: (f32, f32),
+    pub opacity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    opacity: f32,
+}
+
+impl Vertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const F32X2: wgpu::BufferAddress = std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress;
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: F32X2,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: F32X2 * 2,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+struct SpriteTexture {
+    image: Weak<RgbaImage>,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Vertices for one frame's sprites and the texture each quad samples.
+pub struct SpriteBatch {
+    vertex_buffer: wgpu::Buffer,
+    textures: Vec<usize>,
+}
+
+/// Draws RGBA images as textured quads with alpha blending.
+pub struct SpriteRenderer {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+    textures: HashMap<usize, SpriteTexture>,
+}
+
+impl SpriteRenderer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("Sprite Bind Group Layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "shaders/sprite.wgsl"
+            ))),
+        });
+        Self {
+            bind_group_layout,
+            sampler,
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Uploads `image` unless it already has a texture, and returns its key.
+    fn texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &Rc<RgbaImage>,
+    ) -> usize {
+        let key = Rc::as_ptr(image) as usize;
+        if self.textures.contains_key(&key) {
+            return key;
+        }
+        // Images are shrunk when loaded, this only keeps a stray one from
+        // failing the upload.
+        let limit = device.limits().max_texture_dimension_2d;
+        let fitted;
+        let mut pixels: &RgbaImage = image;
+        if image.width() > limit || image.height() > limit {
+            let scale = limit as f32 / image.width().max(image.height()) as f32;
+            let width = ((image.width() as f32 * scale) as u32).clamp(1, limit);
+            let height = ((image.height() as f32 * scale) as u32).clamp(1, limit);
+            tracing::warn!(
+                "Shrinking a {}x{} sprite to {}x{} to fit in a texture",
+                image.width(),
+                image.height(),
+                width,
+                height
+            );
+            fitted = resize(image.as_ref(), width, height, FilterType::Triangle);
+            pixels = &fitted;
+        }
+        let size = wgpu::Extent3d {
+            width: pixels.width(),
+            height: pixels.height(),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sprite Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * pixels.width()),
+                rows_per_image: Some(pixels.height()),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("Sprite Bind Group"),
+        });
+        self.textures.insert(
+            key,
+            SpriteTexture {
+                image: Rc::downgrade(image),
+                bind_group,
+            },
+        );
+        key
+    }
+
+    /// Uploads new images and builds the quads for `sprites`, or returns
+    /// `None` when there is nothing to draw.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sprites: &[Sprite],
+        target_size: (u32, u32),
+    ) -> Option<SpriteBatch> {
+        // A freed image's address can be reused by a new one, so forget
+        // textures whose image is gone before looking anything up.
+        self.textures
+            .retain(|_, texture| texture.image.strong_count() > 0);
+        if sprites.is_empty() {
+            return None;
+        }
+
+        let (width, height) = (target_size.0 as f32, target_size.1 as f32);
+        let to_ndc = |x: f32, y: f32| [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0];
+        let mut vertices = Vec::with_capacity(sprites.len() * 6);
+        let mut textures = Vec::with_capacity(sprites.len());
+        for sprite in sprites {
+            textures.push(self.texture(device, queue, &sprite.image));
+            let (left, top) = sprite.position;
+            let (right, bottom) = (left + sprite.size.0, top + sprite.size.1);
+            let corner = |x: f32, y: f32, u: f32, v: f32| Vertex {
+                position: to_ndc(x, y),
+                tex_coords: [u, v],
+                opacity: sprite.opacity,
+            };
+            let top_left = corner(left, top, 0.0, 0.0);
+            let top_right = corner(right, top, 1.0, 0.0);
+            let bottom_left = corner(left, bottom, 0.0, 1.0);
+            let bottom_right = corner(right, bottom, 1.0, 1.0);
+            vertices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                bottom_right,
+                bottom_right,
+                top_right,
+                top_left,
+            ]);
+        }
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sprite Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Some(SpriteBatch {
+            vertex_buffer,
+            textures,
+        })
+    }
+
+    fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> &wgpu::RenderPipeline {
+        self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Sprite Pipeline"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_main",
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        })
+    }
+
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        format: wgpu::TextureFormat,
+        batch: &SpriteBatch,
+    ) {
+        render_pass.set_pipeline(self.pipeline(device, format));
+        render_pass.set_vertex_buffer(0, batch.vertex_buffer.slice(..));
+        for (index, key) in batch.textures.iter().enumerate() {
+            let first = index as u32 * 6;
+            render_pass.set_bind_group(0, &self.textures[key].bind_group, &[]);
+            render_pass.draw(first..first + 6, 0..1);
+        }
+    }
+}
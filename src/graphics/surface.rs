@@ -1,9 +1,11 @@
-use smithay_client_toolkit::session_lock::SessionLockSurface;
-use wgpu::{Surface, SurfaceConfiguration, Texture, TextureUsages};
+use smithay_client_toolkit::{
+    reexports::client::protocol::wl_surface::WlSurface, session_lock::SessionLockSurface,
+};
+use wgpu::{Surface, SurfaceConfiguration, Texture, TextureFormat, TextureUsages};
 
 use crate::media::Media;
 
-use super::Graphics;
+use super::{overlay::Target, Graphics};
 
 pub struct LockSurfaceWrapper {
     surface: Option<Surface<'static>>,
@@ -14,8 +16,15 @@ pub struct LockSurfaceWrapper {
     pub bind_group: Option<wgpu::BindGroup>,
     pub pipeline: Option<wgpu::RenderPipeline>,
     size: (u32, u32),
-    _lock_surface: SessionLockSurface,
+    scale_factor: u32,
+    configured: bool,
+    lock_surface: SessionLockSurface,
+    output_name: Option<String>,
     pub media: Media,
+    /// Clickable areas of the last frame.
+    pub targets: Vec<Target>,
+    /// Set while a frame callback is on its way.
+    pub frame_pending: bool,
 }
 
 impl LockSurfaceWrapper {
@@ -35,7 +44,11 @@ impl LockSurfaceWrapper {
         self.bind_group.as_ref().unwrap()
     }
 
-    pub fn new(lock_surface: SessionLockSurface, media: Media) -> Self {
+    pub fn new(
+        lock_surface: SessionLockSurface,
+        media: Media,
+        output_name: Option<String>,
+    ) -> Self {
         Self {
             surface: None,
             config: None,
@@ -45,8 +58,13 @@ impl LockSurfaceWrapper {
             bind_group: None,
             pipeline: None,
             size: (0, 0),
-            _lock_surface: lock_surface,
+            scale_factor: 1,
+            configured: false,
+            lock_surface,
+            output_name,
             media,
+            targets: Vec::new(),
+            frame_pending: false,
         }
     }
     pub fn init(
@@ -88,6 +106,16 @@ impl LockSurfaceWrapper {
         self.texture.as_ref().unwrap()
     }
 
+    /// Whether the compositor has configured this surface and its media is
+    /// ready to draw.
+    pub fn is_configured(&self) -> bool {
+        self.configured
+    }
+
+    pub fn set_configured(&mut self) {
+        self.configured = true;
+    }
+
     pub fn resize(&mut self, gcontext: &Graphics, size: (u32, u32)) {
         let config = self.config.as_mut().unwrap();
         config.width = size.0;
@@ -97,6 +125,30 @@ impl LockSurfaceWrapper {
         self.size = size;
     }
 
+    pub fn wl_surface(&self) -> &WlSurface {
+        self.lock_surface.wl_surface()
+    }
+
+    /// Name of the output this surface covers, such as `DP-1`.
+    pub fn output_name(&self) -> Option<&str> {
+        self.output_name.as_deref()
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.config.as_ref().unwrap().format
+    }
+
+    /// Integer scale of the output this surface is on; `size` is in physical
+    /// pixels, i.e. the logical size multiplied by this.
+    pub fn scale_factor(&self) -> u32 {
+        self.scale_factor
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: u32) {
+        self.scale_factor = scale_factor.max(1);
+        self.wl_surface().set_buffer_scale(self.scale_factor as i32);
+    }
+
     pub fn get_surface(&self) -> &Surface {
         self.surface.as_ref().unwrap()
     }
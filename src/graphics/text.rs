@@ -0,0 +1,632 @@
+use std::{collections::HashMap, str::FromStr};
+
+use ab_glyph::{point, Font, FontVec, Glyph, GlyphId, PxScale, ScaleFont};
+use fontdb::{Database, Family, Query, Style, Weight};
+use wgpu::util::DeviceExt;
+
+const ATLAS_SIZE: u32 = 1024;
+const ATLAS_PADDING: u32 = 1;
+const FALLBACK_FAMILY: Family<'static> = Family::SansSerif;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl FromStr for Align {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Align::Left),
+            "center" => Ok(Align::Center),
+            "right" => Ok(Align::Right),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How a block of text looks. `font` is a fontconfig-style pattern such as
+/// `sans-serif`, `DejaVu Sans Mono` or `Noto Sans:bold:italic`, and `size`
+/// is in points before the output scale factor is applied.
+#[derive(Debug, Clone)]
+pub struct TextStyle {
+    pub font: String,
+    pub size: f32,
+    pub color: wgpu::Color,
+    pub align: Align,
+}
+
+/// Text to draw on a surface. `position` is in physical pixels from the top
+/// left corner; x is the left edge, center or right edge of every line
+/// depending on `style.align`, and y is the top of the first line.
+#[derive(Debug, Clone)]
+pub struct TextSection {
+    pub text: String,
+    pub style: TextStyle,
+    pub position: (f32, f32),
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+impl Vertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress * 2,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    glyph: GlyphId,
+    /// Pixel size in quarter pixels, so nearby sizes share atlas entries.
+    size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AtlasEntry {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    /// Top left of the bitmap relative to the glyph origin on the baseline.
+    offset: (f32, f32),
+    size: (f32, f32),
+}
+
+/// Rasterized glyphs packed into rows of a single-channel texture. When it
+/// fills up everything is thrown away and rasterized again on demand.
+struct GlyphAtlas {
+    texture: wgpu::Texture,
+    cursor: (u32, u32),
+    row_height: u32,
+    /// `None` marks glyphs without an outline, such as spaces.
+    entries: HashMap<GlyphKey, Option<AtlasEntry>>,
+}
+
+impl GlyphAtlas {
+    fn new(device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        Self {
+            texture,
+            cursor: (0, 0),
+            row_height: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        tracing::trace!("Glyph atlas full, clearing");
+        self.cursor = (0, 0);
+        self.row_height = 0;
+        self.entries.clear();
+    }
+
+    /// Finds room for a `width` x `height` bitmap, or `None` if the atlas is full.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if self.cursor.0 + width + ATLAS_PADDING > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + ATLAS_PADDING);
+            self.row_height = 0;
+        }
+        if width + ATLAS_PADDING > ATLAS_SIZE || self.cursor.1 + height + ATLAS_PADDING > ATLAS_SIZE
+        {
+            return None;
+        }
+        let origin = self.cursor;
+        self.cursor.0 += width + ATLAS_PADDING;
+        self.row_height = self.row_height.max(height);
+        Some(origin)
+    }
+
+    /// Returns the atlas entry for `key`, rasterizing it first if needed.
+    /// The outer `None` means the atlas is full.
+    fn glyph(
+        &mut self,
+        queue: &wgpu::Queue,
+        font: &FontVec,
+        key: GlyphKey,
+        scale: PxScale,
+    ) -> Option<Option<AtlasEntry>> {
+        if let Some(entry) = self.entries.get(&key) {
+            return Some(*entry);
+        }
+
+        let glyph = Glyph {
+            id: key.glyph,
+            scale,
+            position: point(0.0, 0.0),
+        };
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            self.entries.insert(key, None);
+            return Some(None);
+        };
+        let bounds = outlined.px_bounds();
+        let width = bounds.width().ceil() as u32;
+        let height = bounds.height().ceil() as u32;
+        if width == 0 || height == 0 {
+            self.entries.insert(key, None);
+            return Some(None);
+        }
+
+        let (x, y) = self.allocate(width, height)?;
+        let mut coverage = vec![0u8; (width * height) as usize];
+        outlined.draw(|gx, gy, c| {
+            if gx < width && gy < height {
+                coverage[(gy * width + gx) as usize] = (c.clamp(0.0, 1.0) * 255.0) as u8;
+            }
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &coverage,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let entry = AtlasEntry {
+            uv_min: [x as f32 / ATLAS_SIZE as f32, y as f32 / ATLAS_SIZE as f32],
+            uv_max: [
+                (x + width) as f32 / ATLAS_SIZE as f32,
+                (y + height) as f32 / ATLAS_SIZE as f32,
+            ],
+            offset: (bounds.min.x, bounds.min.y),
+            size: (width as f32, height as f32),
+        };
+        self.entries.insert(key, Some(entry));
+        Some(Some(entry))
+    }
+}
+
+struct PositionedGlyph {
+    font: usize,
+    id: GlyphId,
+    scale: PxScale,
+    /// Glyph origin on the baseline, in physical pixels.
+    origin: (f32, f32),
+    color: wgpu::Color,
+}
+
+/// Vertices for one frame's text, ready to be drawn into a render pass.
+pub struct TextBatch {
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+/// Draws text with system fonts through a shared glyph atlas on the
+/// `Graphics` device.
+pub struct TextRenderer {
+    database: Database,
+    fonts: Vec<FontVec>,
+    font_ids: HashMap<String, Option<usize>>,
+    atlas: GlyphAtlas,
+    bind_group: wgpu::BindGroup,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl TextRenderer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let mut database = Database::new();
+        database.load_system_fonts();
+        tracing::trace!("Loaded {} font faces", database.len());
+
+        let atlas = GlyphAtlas::new(device);
+        let atlas_view = atlas
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("Text Bind Group Layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("Text Bind Group"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "shaders/text.wgsl"
+            ))),
+        });
+
+        Self {
+            database,
+            fonts: Vec::new(),
+            font_ids: HashMap::new(),
+            atlas,
+            bind_group,
+            pipeline_layout,
+            shader,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Resolves a fontconfig-style pattern (`family[:bold][:italic]`) to a
+    /// loaded font, falling back to the sans-serif family.
+    fn font(&mut self, pattern: &str) -> Option<usize> {
+        if let Some(id) = self.font_ids.get(pattern) {
+            return *id;
+        }
+
+        let mut parts = pattern.split(':').map(str::trim);
+        let family = match parts.next().unwrap_or_default() {
+            "" | "sans-serif" | "sans" => Family::SansSerif,
+            "serif" => Family::Serif,
+            "monospace" | "mono" => Family::Monospace,
+            "cursive" => Family::Cursive,
+            "fantasy" => Family::Fantasy,
+            name => Family::Name(name),
+        };
+        let mut weight = Weight::NORMAL;
+        let mut style = Style::Normal;
+        for attribute in parts {
+            match attribute {
+                "bold" => weight = Weight::BOLD,
+                "light" => weight = Weight::LIGHT,
+                "italic" => style = Style::Italic,
+                "oblique" => style = Style::Oblique,
+                other => tracing::warn!("Unknown font attribute '{}' in '{}'", other, pattern),
+            }
+        }
+
+        let families = [family, FALLBACK_FAMILY];
+        let id = self
+            .database
+            .query(&Query {
+                families: &families,
+                weight,
+                style,
+                ..Query::default()
+            })
+            .and_then(|face| {
+                self.database.with_face_data(face, |data, index| {
+                    FontVec::try_from_vec_and_index(data.to_vec(), index).ok()
+                })
+            })
+            .flatten()
+            .map(|font| {
+                self.fonts.push(font);
+                self.fonts.len() - 1
+            });
+        if id.is_none() {
+            tracing::error!("No font found for '{}'", pattern);
+        }
+        self.font_ids.insert(pattern.to_string(), id);
+        id
+    }
+
+    fn px_scale(&self, font: usize, size: f32, scale_factor: f32) -> PxScale {
+        self.fonts[font]
+            .pt_to_px_scale(size * scale_factor)
+            .unwrap_or(PxScale::from(size * scale_factor))
+    }
+
+    /// Lays out every line of `section`, returning the glyphs and the size of
+    /// the block in physical pixels.
+    fn layout(
+        &mut self,
+        section: &TextSection,
+        scale_factor: f32,
+    ) -> (Vec<PositionedGlyph>, (f32, f32)) {
+        let Some(font_index) = self.font(&section.style.font) else {
+            return (Vec::new(), (0.0, 0.0));
+        };
+        let scale = self.px_scale(font_index, section.style.size, scale_factor);
+        let font = self.fonts[font_index].as_scaled(scale);
+        let line_height = font.height() + font.line_gap();
+
+        let mut glyphs = Vec::new();
+        let mut block_width: f32 = 0.0;
+        let mut baseline = section.position.1 + font.ascent();
+        let mut lines = 0;
+        for line in section.text.lines() {
+            let line_start = glyphs.len();
+            let mut pen = 0.0;
+            let mut previous: Option<GlyphId> = None;
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    pen += font.kern(previous, id);
+                }
+                glyphs.push(PositionedGlyph {
+                    font: font_index,
+                    id,
+                    scale,
+                    origin: (pen, baseline),
+                    color: section.style.color,
+                });
+                pen += font.h_advance(id);
+                previous = Some(id);
+            }
+
+            let x = match section.style.align {
+                Align::Left => section.position.0,
+                Align::Center => section.position.0 - pen / 2.0,
+                Align::Right => section.position.0 - pen,
+            };
+            for glyph in &mut glyphs[line_start..] {
+                glyph.origin.0 += x;
+            }
+            block_width = block_width.max(pen);
+            baseline += line_height;
+            lines += 1;
+        }
+        (glyphs, (block_width, line_height * lines as f32))
+    }
+
+    /// Size of `section` in physical pixels once laid out.
+    pub fn measure(&mut self, section: &TextSection, scale_factor: f32) -> (f32, f32) {
+        self.layout(section, scale_factor).1
+    }
+
+    /// Re-breaks `text` so no line is wider than `max_width` physical pixels
+    /// in `style`, splitting at spaces. Existing line breaks are kept and a
+    /// single word wider than `max_width` gets a line to itself.
+    pub fn wrap(
+        &mut self,
+        text: &str,
+        style: &TextStyle,
+        max_width: f32,
+        scale_factor: f32,
+    ) -> String {
+        let mut probe = TextSection {
+            text: String::new(),
+            style: style.clone(),
+            position: (0.0, 0.0),
+        };
+        let mut lines = Vec::new();
+        for paragraph in text.lines() {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                if line.is_empty() {
+                    line.push_str(word);
+                    continue;
+                }
+                probe.text = format!("{} {}", line, word);
+                if self.measure(&probe, scale_factor).0 > max_width {
+                    lines.push(std::mem::replace(&mut line, word.to_string()));
+                } else {
+                    line = std::mem::take(&mut probe.text);
+                }
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    fn vertices(
+        &mut self,
+        queue: &wgpu::Queue,
+        sections: &[TextSection],
+        scale_factor: f32,
+        target_size: (u32, u32),
+    ) -> Option<Vec<Vertex>> {
+        let (width, height) = (target_size.0 as f32, target_size.1 as f32);
+        let to_ndc = |x: f32, y: f32| [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0];
+
+        let mut vertices = Vec::new();
+        for section in sections {
+            let (glyphs, _) = self.layout(section, scale_factor);
+            for glyph in glyphs {
+                let key = GlyphKey {
+                    font: glyph.font,
+                    glyph: glyph.id,
+                    size: (glyph.scale.y * 4.0).round() as u32,
+                };
+                let Some(entry) =
+                    self.atlas
+                        .glyph(queue, &self.fonts[glyph.font], key, glyph.scale)?
+                else {
+                    continue;
+                };
+
+                let left = (glyph.origin.0 + entry.offset.0).round();
+                let top = (glyph.origin.1 + entry.offset.1).round();
+                let right = left + entry.size.0;
+                let bottom = top + entry.size.1;
+                let color = [
+                    glyph.color.r as f32,
+                    glyph.color.g as f32,
+                    glyph.color.b as f32,
+                    glyph.color.a as f32,
+                ];
+                let corner = |x: f32, y: f32, u: f32, v: f32| Vertex {
+                    position: to_ndc(x, y),
+                    tex_coords: [u, v],
+                    color,
+                };
+                let top_left = corner(left, top, entry.uv_min[0], entry.uv_min[1]);
+                let top_right = corner(right, top, entry.uv_max[0], entry.uv_min[1]);
+                let bottom_left = corner(left, bottom, entry.uv_min[0], entry.uv_max[1]);
+                let bottom_right = corner(right, bottom, entry.uv_max[0], entry.uv_max[1]);
+                vertices.extend_from_slice(&[
+                    top_left,
+                    bottom_left,
+                    bottom_right,
+                    bottom_right,
+                    top_right,
+                    top_left,
+                ]);
+            }
+        }
+        Some(vertices)
+    }
+
+    /// Rasterizes any missing glyphs and builds the vertices for `sections`.
+    /// Returns `None` when there is nothing to draw.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sections: &[TextSection],
+        scale_factor: f32,
+        target_size: (u32, u32),
+    ) -> Option<TextBatch> {
+        if sections.is_empty() {
+            return None;
+        }
+        let vertices = match self.vertices(queue, sections, scale_factor, target_size) {
+            Some(vertices) => vertices,
+            None => {
+                // Glyphs already placed for this frame would be evicted by
+                // clearing mid-way, so clear once and lay everything out again.
+                self.atlas.clear();
+                self.vertices(queue, sections, scale_factor, target_size)?
+            }
+        };
+        if vertices.is_empty() {
+            return None;
+        }
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Some(TextBatch {
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+        })
+    }
+
+    fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> &wgpu::RenderPipeline {
+        self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Text Pipeline"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_main",
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        })
+    }
+
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        format: wgpu::TextureFormat,
+        batch: &TextBatch,
+    ) {
+        render_pass.set_pipeline(self.pipeline(device, format));
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, batch.vertex_buffer.slice(..));
+        render_pass.draw(0..batch.vertex_count, 0..1);
+    }
+}
@@ -13,9 +13,22 @@ impl CompositorHandler for AppData {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_factor: i32,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
     ) {
+        let Some(index) = self.surface_index(surface) else {
+            return;
+        };
+        let lock_surface = &mut self.lock_data.session_lock_surfaces[index];
+        let old_factor = lock_surface.scale_factor();
+        if old_factor == new_factor.max(1) as u32 {
+            return;
+        }
+        tracing::trace!("Scale factor changed {} -> {}", old_factor, new_factor);
+        let (width, height) = lock_surface.size();
+        lock_surface.set_scale_factor(new_factor as u32);
+        self.configure_surface(index, (width / old_factor, height / old_factor));
+        self.render_surface(index);
     }
 
     fn transform_changed(
@@ -31,9 +44,12 @@ impl CompositorHandler for AppData {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
+        surface: &wl_surface::WlSurface,
         _time: u32,
     ) {
+        if let Some(index) = self.surface_index(surface) {
+            self.transition_frame(index);
+        }
     }
 
     fn surface_enter(
@@ -1,20 +1,40 @@
+use std::{cell::Cell, io::Read, rc::Rc, time::Duration};
+
 use smithay_client_toolkit::{
-    reexports::client::{protocol::wl_surface, Connection, QueueHandle},
-    seat::keyboard::{KeyboardHandler, Keysym},
+    reexports::{
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            PostAction, RegistrationToken,
+        },
+        client::{
+            protocol::{wl_keyboard::WlKeyboard, wl_surface},
+            Connection, QueueHandle,
+        },
+    },
+    seat::keyboard::{KeyEvent, KeyboardHandler, Keysym, RepeatInfo},
 };
 
 use crate::app::AppData;
+use crate::keyboard::Composed;
+use crate::lock::AuthState;
+
+/// Clipboard types read when pasting, in order of preference.
+const PASTE_MIME_TYPES: [&str; 3] = ["text/plain;charset=utf-8", "text/plain", "UTF8_STRING"];
+/// Nobody pastes a password longer than this, so the rest is not read.
+const PASTE_MAX: usize = 4096;
+/// A clipboard owner that hasn't finished writing by then never will.
+const PASTE_TIMEOUT: Duration = Duration::from_secs(2);
 
 impl KeyboardHandler for AppData {
     fn enter(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
+        _keyboard: &WlKeyboard,
         _surface: &wl_surface::WlSurface,
         _serial: u32,
         _raw: &[u32],
-        _keysyms: &[smithay_client_toolkit::seat::keyboard::Keysym],
+        _keysyms: &[Keysym],
     ) {
     }
 
@@ -22,40 +42,43 @@ impl KeyboardHandler for AppData {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
+        keyboard: &WlKeyboard,
         _surface: &wl_surface::WlSurface,
         _serial: u32,
     ) {
+        self.stop_key_repeat_from(keyboard);
+        if let Some(state) = self.keyboard_state_mut(keyboard) {
+            state.reset_compose();
+        }
     }
 
     fn press_key(
         &mut self,
-        conn: &Connection,
+        _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
+        keyboard: &WlKeyboard,
         _serial: u32,
-        event: smithay_client_toolkit::seat::keyboard::KeyEvent,
+        event: KeyEvent,
     ) {
-        match event.keysym {
-            Keysym::Return => {
-                match self.lock_data.unlock_with_auth() {
-                    Ok(_) => {
-                        tracing::trace!("Authenticated, unlocked!");
-                        conn.roundtrip().unwrap();
-                        self.exit = true;
-                    }
-                    Err(e) => tracing::warn!("{e}"),
-                };
-            }
-            Keysym::BackSpace => {
-                self.lock_data.password_buffer.pop();
-            }
-            _ => {
-                let key_char = event.keysym.key_char();
-                if let Some(k) = key_char {
-                    self.lock_data.password_buffer.push(k);
-                }
-            }
+        self.wake();
+        if self.grace_unlock(false) {
+            return;
+        }
+        // Only the last key pressed repeats, whichever keyboard it is on.
+        self.stop_key_repeat();
+        self.wayland.active_seat = self
+            .wayland
+            .seats
+            .iter()
+            .find(|s| s.keyboard.as_ref() == Some(keyboard))
+            .map(|s| s.seat.clone());
+        let Some(state) = self.keyboard_state_mut(keyboard) else {
+            return;
+        };
+        state.update_key(&event, true);
+        let keysym = state.keysym(&event);
+        if self.handle_key(keysym) {
+            self.start_key_repeat(keyboard, event);
         }
     }
 
@@ -63,39 +86,284 @@ impl KeyboardHandler for AppData {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
+        keyboard: &WlKeyboard,
         _serial: u32,
-        _event: smithay_client_toolkit::seat::keyboard::KeyEvent,
+        event: KeyEvent,
     ) {
+        if self
+            .lock_data
+            .key_repeat
+            .as_ref()
+            .is_some_and(|(repeating, raw_code, _)| {
+                repeating == keyboard && *raw_code == event.raw_code
+            })
+        {
+            self.stop_key_repeat();
+        }
+        if let Some(state) = self.keyboard_state_mut(keyboard) {
+            state.update_key(&event, false);
+        }
     }
 
     fn update_modifiers(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
+        keyboard: &WlKeyboard,
         _serial: u32,
-        _modifiers: smithay_client_toolkit::seat::keyboard::Modifiers,
-        _layout: u32,
+        modifiers: smithay_client_toolkit::seat::keyboard::Modifiers,
+        layout: u32,
     ) {
+        if self
+            .keyboard_state_mut(keyboard)
+            .is_some_and(|state| state.set_modifiers(&modifiers, layout))
+        {
+            self.render_all();
+        }
     }
 
     fn update_repeat_info(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
-        _info: smithay_client_toolkit::seat::keyboard::RepeatInfo,
+        keyboard: &WlKeyboard,
+        info: RepeatInfo,
     ) {
+        if let Some(state) = self.keyboard_state_mut(keyboard) {
+            state.set_repeat_info(info);
+        }
     }
 
     fn update_keymap(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
-        _keymap: smithay_client_toolkit::seat::keyboard::Keymap<'_>,
+        keyboard: &WlKeyboard,
+        keymap: smithay_client_toolkit::seat::keyboard::Keymap<'_>,
     ) {
+        if let Some(state) = self.keyboard_state_mut(keyboard) {
+            state.set_keymap(keymap.as_string());
+            self.render_all();
+        }
+    }
+}
+
+impl AppData {
+    /// Acts on a key pressed on a keyboard or on the lock screen itself.
+    /// Returns whether holding the key should repeat it.
+    pub fn handle_key(&mut self, keysym: Keysym) -> bool {
+        // Already unlocking.
+        if self.lock_data.transition.is_leaving() {
+            return false;
+        }
+        if self.lock_data.keyboard.is_cycle_key(keysym) {
+            if let Some(state) = self.active_keyboard_mut() {
+                state.cycle_layout();
+            }
+            self.render_all();
+            return false;
+        }
+        if self.lock_data.layout.key(keysym) {
+            self.render_all();
+            return false;
+        }
+        if keysym == Keysym::Return {
+            if let Some(state) = self.active_keyboard_mut() {
+                state.reset_compose();
+            }
+            self.set_auth_state(AuthState::Verifying);
+            match self.lock_data.unlock_with_auth() {
+                Ok(_) => {
+                    tracing::trace!("Authenticated");
+                    self.unlock();
+                }
+                Err(e) => {
+                    tracing::warn!("{e}");
+                    let e = e.to_string();
+                    self.lock_data.feedback.push(e);
+                    self.show_feedback();
+                    self.set_auth_state(AuthState::Wrong);
+                    self.idle_after_failure();
+                }
+            };
+            return false;
+        }
+        let ctrl = self.active_keyboard().is_some_and(|state| state.ctrl);
+        if ctrl && matches!(keysym, Keysym::v | Keysym::V) {
+            self.paste();
+            return false;
+        }
+        let keysym = if ctrl {
+            keysym
+        } else {
+            let composed = self
+                .active_keyboard_mut()
+                .map_or(Composed::Key(keysym), |state| state.compose(keysym));
+            match composed {
+                Composed::Key(keysym) => keysym,
+                Composed::Pending => return false,
+                Composed::Text(text) => {
+                    self.type_text(&text);
+                    return false;
+                }
+            }
+        };
+        self.edit_password(keysym)
+    }
+
+    /// Applies a key that edits the password, readline style. Returns
+    /// false for keys that don't, including any other Ctrl combination.
+    fn edit_password(&mut self, keysym: Keysym) -> bool {
+        let ctrl = self.active_keyboard().is_some_and(|state| state.ctrl);
+        match keysym {
+            Keysym::BackSpace if ctrl => self.lock_data.delete_word(),
+            Keysym::BackSpace => {
+                self.lock_data.password_buffer.pop();
+            }
+            Keysym::Escape => self.lock_data.password_buffer.clear(),
+            Keysym::u | Keysym::U if ctrl => self.lock_data.password_buffer.clear(),
+            Keysym::w | Keysym::W if ctrl => self.lock_data.delete_word(),
+            _ if ctrl => return false,
+            _ => match keysym.key_char().filter(|k| !k.is_control()) {
+                Some(k) => {
+                    self.lock_data.password_buffer.push(k);
+                    self.set_auth_state(AuthState::Typing);
+                    return true;
+                }
+                None => return false,
+            },
+        }
+        self.set_auth_state(AuthState::Clearing);
+        true
+    }
+
+    /// Adds `text` from a paste, a compose sequence or an input method to
+    /// the password, leaving out control characters.
+    pub fn type_text(&mut self, text: &str) {
+        let before = self.lock_data.password_buffer.len();
+        self.lock_data
+            .password_buffer
+            .extend(text.chars().filter(|c| !c.is_control()));
+        if self.lock_data.password_buffer.len() > before {
+            self.set_auth_state(AuthState::Typing);
+        }
+    }
+
+    /// Reads the clipboard on the event loop and types its first line,
+    /// if pasting is allowed.
+    fn paste(&mut self) {
+        let Some(device) = self
+            .wayland
+            .seats
+            .iter()
+            .find(|s| self.wayland.active_seat.as_ref() == Some(&s.seat))
+            .and_then(|s| s.data_device.as_ref())
+        else {
+            return;
+        };
+        let Some(offer) = device.data().selection_offer() else {
+            return;
+        };
+        let Some(mime) = offer.with_mime_types(|types| {
+            PASTE_MIME_TYPES
+                .iter()
+                .find(|mime| types.iter().any(|t| t == *mime))
+                .map(|mime| mime.to_string())
+        }) else {
+            return;
+        };
+        let pipe = match offer.receive(mime) {
+            Ok(pipe) => pipe,
+            Err(e) => {
+                tracing::warn!("Unable to read the clipboard: {}", e);
+                return;
+            }
+        };
+        let mut contents = Vec::new();
+        let timer: Rc<Cell<Option<RegistrationToken>>> = Rc::default();
+        let finished = timer.clone();
+        let reader = self
+            .wayland
+            .loop_handle
+            .insert_source(pipe, move |_, file, app_data| {
+                let mut buffer = [0; 4096];
+                // SAFETY: the fd is only read, never closed or replaced.
+                let read = unsafe { file.get_mut() }.read(&mut buffer).unwrap_or(0);
+                if read > 0 {
+                    if contents.len() < PASTE_MAX {
+                        contents.extend_from_slice(&buffer[..read]);
+                    }
+                    return PostAction::Continue;
+                }
+                if let Some(timer) = finished.take() {
+                    app_data.wayland.loop_handle.remove(timer);
+                }
+                let text = String::from_utf8_lossy(&contents);
+                app_data.type_text(text.lines().next().unwrap_or_default());
+                PostAction::Remove
+            })
+            .expect("Failed to insert the clipboard source!");
+        let token = self
+            .wayland
+            .loop_handle
+            .insert_source(
+                Timer::from_duration(PASTE_TIMEOUT),
+                move |_, _, app_data| {
+                    tracing::warn!("Gave up reading the clipboard");
+                    app_data.wayland.loop_handle.remove(reader);
+                    TimeoutAction::Drop
+                },
+            )
+            .expect("Failed to insert the clipboard timer!");
+        timer.set(Some(token));
+    }
+
+    /// Repeats `event` for as long as it is held, at the compositor's rate.
+    fn start_key_repeat(&mut self, keyboard: &WlKeyboard, event: KeyEvent) {
+        let Some((delay, interval)) = self
+            .keyboard_state_mut(keyboard)
+            .and_then(|state| state.repeat(&event))
+        else {
+            return;
+        };
+        let raw_code = event.raw_code;
+        let repeating = keyboard.clone();
+        let token = self
+            .wayland
+            .loop_handle
+            .insert_source(Timer::from_duration(delay), move |_, _, app_data| {
+                // Translated again each time, as held modifiers may have
+                // changed since the press.
+                let keysym = app_data
+                    .keyboard_state_mut(&repeating)
+                    .map_or(event.keysym, |state| state.keysym(&event));
+                if app_data.edit_password(keysym) {
+                    TimeoutAction::ToDuration(interval)
+                } else {
+                    app_data.lock_data.key_repeat = None;
+                    TimeoutAction::Drop
+                }
+            })
+            .expect("Failed to insert the key repeat timer!");
+        self.lock_data.key_repeat = Some((keyboard.clone(), raw_code, token));
+    }
+
+    pub fn stop_key_repeat(&mut self) {
+        if let Some((_, _, token)) = self.lock_data.key_repeat.take() {
+            self.wayland.loop_handle.remove(token);
+        }
+    }
+
+    /// Stops repeating if the key being repeated is on `keyboard`.
+    pub fn stop_key_repeat_from(&mut self, keyboard: &WlKeyboard) {
+        if self
+            .lock_data
+            .key_repeat
+            .as_ref()
+            .is_some_and(|(repeating, _, _)| repeating == keyboard)
+        {
+            self.stop_key_repeat();
+        }
     }
 }
 
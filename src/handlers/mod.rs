@@ -1,6 +1,11 @@
 mod compositor;
+mod data_device;
 mod keyboard;
 mod output;
+mod output_power;
+pub mod pointer;
 mod registry;
-mod seat;
+pub mod seat;
 mod session_lock;
+pub mod text_input;
+mod touch;
@@ -0,0 +1,27 @@
+use smithay_client_toolkit::reexports::{
+    client::{delegate_noop, Connection, Dispatch, QueueHandle},
+    protocols_wlr::output_power_management::v1::client::{
+        zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+        zwlr_output_power_v1::{self, ZwlrOutputPowerV1},
+    },
+};
+
+use crate::app::AppData;
+
+impl Dispatch<ZwlrOutputPowerV1, ()> for AppData {
+    fn event(
+        _state: &mut Self,
+        _power: &ZwlrOutputPowerV1,
+        event: zwlr_output_power_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // The output stays on, which is harmless.
+        if let zwlr_output_power_v1::Event::Failed = event {
+            tracing::warn!("Unable to turn an idle output off");
+        }
+    }
+}
+
+delegate_noop!(AppData: ignore ZwlrOutputPowerManagerV1);
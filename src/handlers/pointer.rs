@@ -0,0 +1,186 @@
+use std::str::FromStr;
+
+use smithay_client_toolkit::{
+    reexports::{
+        client::{
+            protocol::{wl_pointer::WlPointer, wl_surface::WlSurface},
+            Connection, QueueHandle,
+        },
+        protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::{
+            Shape, WpCursorShapeDeviceV1,
+        },
+    },
+    seat::pointer::{PointerEvent, PointerEventKind, PointerHandler},
+};
+
+use crate::app::AppData;
+use crate::widgets::Action;
+
+/// `BTN_LEFT` from linux/input-event-codes.h.
+const BTN_LEFT: u32 = 0x110;
+
+/// What the pointer looks like over the lock screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cursor {
+    Hidden,
+    /// The theme's arrow, and a hand over anything clickable.
+    Default,
+}
+
+impl FromStr for Cursor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hidden" => Ok(Cursor::Hidden),
+            "default" => Ok(Cursor::Default),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct Pointer {
+    pub pointer: WlPointer,
+    /// Set when the compositor has cursor-shape-v1.
+    pub shape_device: Option<WpCursorShapeDeviceV1>,
+    /// Serial of the last enter, which cursor changes must refer to.
+    serial: u32,
+    hovering: bool,
+}
+
+impl Pointer {
+    pub fn new(pointer: WlPointer, shape_device: Option<WpCursorShapeDeviceV1>) -> Self {
+        Self {
+            pointer,
+            shape_device,
+            serial: 0,
+            hovering: false,
+        }
+    }
+}
+
+impl PointerHandler for AppData {
+    fn pointer_frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        pointer: &WlPointer,
+        events: &[PointerEvent],
+    ) {
+        self.wake();
+        // Entering is left out, as the compositor sends it when the lock
+        // surface appears under a pointer that never moved.
+        let moved = events.iter().any(|event| {
+            matches!(
+                event.kind,
+                PointerEventKind::Motion { .. } | PointerEventKind::Press { .. }
+            )
+        });
+        if moved && self.grace_unlock(true) {
+            return;
+        }
+        for event in events {
+            match event.kind {
+                PointerEventKind::Enter { serial } => {
+                    if let Some(pointer) = self.pointer_mut(pointer) {
+                        pointer.serial = serial;
+                    }
+                    self.update_cursor(pointer, &event.surface, event.position, true);
+                }
+                PointerEventKind::Motion { .. } => {
+                    self.update_cursor(pointer, &event.surface, event.position, false);
+                }
+                PointerEventKind::Press {
+                    button: BTN_LEFT, ..
+                } => {
+                    if let Some(action) = self.target(&event.surface, event.position) {
+                        self.run_action(action);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl AppData {
+    /// The action of the topmost target under `position`, in the logical
+    /// coordinates of `surface`.
+    pub fn target(&self, surface: &WlSurface, position: (f64, f64)) -> Option<Action> {
+        let surface = &self.lock_data.session_lock_surfaces[self.surface_index(surface)?];
+        let scale = surface.scale_factor() as f32;
+        let point = (position.0 as f32 * scale, position.1 as f32 * scale);
+        surface
+            .targets
+            .iter()
+            .rev()
+            .find(|target| target.contains(point))
+            .map(|target| target.action)
+    }
+
+    fn pointer_mut(&mut self, pointer: &WlPointer) -> Option<&mut Pointer> {
+        self.wayland
+            .seats
+            .iter_mut()
+            .filter_map(|s| s.pointer.as_mut())
+            .find(|p| p.pointer == *pointer)
+    }
+
+    /// Sets the cursor when `pointer` enters, and switches between the
+    /// arrow and the hand as it moves on and off clickable widgets.
+    fn update_cursor(
+        &mut self,
+        pointer: &WlPointer,
+        surface: &WlSurface,
+        position: (f64, f64),
+        entered: bool,
+    ) {
+        let hovering = self.target(surface, position).is_some();
+        let cursor = self.wayland.cursor;
+        let Some(pointer) = self.pointer_mut(pointer) else {
+            return;
+        };
+        if !entered && pointer.hovering == hovering {
+            return;
+        }
+        pointer.hovering = hovering;
+        match (cursor, &pointer.shape_device) {
+            (Cursor::Hidden, _) => {
+                if entered {
+                    pointer.pointer.set_cursor(pointer.serial, None, 0, 0);
+                }
+            }
+            (Cursor::Default, Some(device)) => {
+                let shape = if hovering {
+                    Shape::Pointer
+                } else {
+                    Shape::Default
+                };
+                device.set_shape(pointer.serial, shape);
+            }
+            // Without cursor-shape-v1 the compositor's cursor stays.
+            (Cursor::Default, None) => {}
+        }
+    }
+
+    /// Does what a clicked or tapped widget asks for.
+    pub fn run_action(&mut self, action: Action) {
+        match action {
+            Action::CycleLayout => {
+                if let Some(state) = self.active_keyboard_mut() {
+                    state.cycle_layout();
+                }
+            }
+            Action::ToggleReveal => self.lock_data.reveal = !self.lock_data.reveal,
+            Action::Shift => self.lock_data.keypad_shift = !self.lock_data.keypad_shift,
+            Action::Key(keysym) => {
+                // Redraws on its own when anything changes.
+                self.handle_key(keysym);
+                return;
+            }
+        }
+        self.render_all();
+    }
+}
+
+smithay_client_toolkit::delegate_pointer!(AppData);
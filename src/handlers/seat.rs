@@ -1,18 +1,127 @@
 use smithay_client_toolkit::{
-    reexports::client::{Connection, QueueHandle},
+    data_device_manager::data_device::DataDevice,
+    reexports::client::{
+        protocol::{wl_keyboard::WlKeyboard, wl_seat::WlSeat, wl_touch::WlTouch},
+        Connection, QueueHandle,
+    },
     seat::{Capability, SeatHandler, SeatState},
 };
 
 use crate::app::AppData;
+use crate::handlers::{pointer::Pointer, text_input::TextInput};
+use crate::keyboard::KeyboardState;
+
+/// Input devices of one seat. Every seat types into the same password,
+/// each with its own modifiers and layout.
+pub struct Seat {
+    pub seat: WlSeat,
+    pub keyboard: Option<WlKeyboard>,
+    pub keyboard_state: Option<KeyboardState>,
+    /// Clipboard of the seat, only there when pasting is allowed.
+    pub data_device: Option<DataDevice>,
+    pub text_input: Option<TextInput>,
+    pub pointer: Option<Pointer>,
+    pub touch: Option<WlTouch>,
+}
+
+impl Seat {
+    fn new(seat: WlSeat) -> Self {
+        Self {
+            seat,
+            keyboard: None,
+            keyboard_state: None,
+            data_device: None,
+            text_input: None,
+            pointer: None,
+            touch: None,
+        }
+    }
+
+    /// Releases the keyboard along with the clipboard and input method
+    /// that came with it.
+    fn release_keyboard(&mut self) {
+        self.data_device = None;
+        if let Some(text_input) = self.text_input.take() {
+            text_input.input.destroy();
+        }
+        if let Some(keyboard) = self.keyboard.take() {
+            keyboard.release();
+        }
+        self.keyboard_state = None;
+    }
+
+    fn release_pointer(&mut self) {
+        if let Some(pointer) = self.pointer.take() {
+            if let Some(device) = pointer.shape_device {
+                device.destroy();
+            }
+            pointer.pointer.release();
+        }
+    }
+
+    fn release_touch(&mut self) {
+        if let Some(touch) = self.touch.take() {
+            touch.release();
+        }
+    }
+}
+
+impl AppData {
+    /// The devices of `seat`, added on first use since seats present at
+    /// startup never go through `new_seat`.
+    fn seat_mut(&mut self, seat: &WlSeat) -> &mut Seat {
+        let seats = &mut self.wayland.seats;
+        let index = match seats.iter().position(|s| s.seat == *seat) {
+            Some(index) => index,
+            None => {
+                seats.push(Seat::new(seat.clone()));
+                seats.len() - 1
+            }
+        };
+        &mut seats[index]
+    }
+
+    /// State of `keyboard`, on whichever seat it is.
+    pub fn keyboard_state_mut(&mut self, keyboard: &WlKeyboard) -> Option<&mut KeyboardState> {
+        self.wayland
+            .seats
+            .iter_mut()
+            .find(|s| s.keyboard.as_ref() == Some(keyboard))
+            .and_then(|s| s.keyboard_state.as_mut())
+    }
+
+    /// The seat whose keyboard was typed on last, or else the first with
+    /// a keyboard. Its modifiers and layout are the ones shown.
+    fn active_keyboard_seat(&self) -> Option<usize> {
+        let seats = &self.wayland.seats;
+        seats
+            .iter()
+            .position(|s| {
+                s.keyboard_state.is_some() && self.wayland.active_seat.as_ref() == Some(&s.seat)
+            })
+            .or_else(|| seats.iter().position(|s| s.keyboard_state.is_some()))
+    }
+
+    pub fn active_keyboard(&self) -> Option<&KeyboardState> {
+        let index = self.active_keyboard_seat()?;
+        self.wayland.seats[index].keyboard_state.as_ref()
+    }
+
+    pub fn active_keyboard_mut(&mut self) -> Option<&mut KeyboardState> {
+        let index = self.active_keyboard_seat()?;
+        self.wayland.seats[index].keyboard_state.as_mut()
+    }
+
+    /// Whether any seat has a keyboard attached.
+    pub fn has_keyboard(&self) -> bool {
+        self.wayland.seats.iter().any(|s| s.keyboard.is_some())
+    }
+}
 
 impl SeatHandler for AppData {
-    fn new_seat(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
-    ) {
+    fn new_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, seat: WlSeat) {
         tracing::debug!("New Seat created!");
+        self.seat_mut(&seat);
     }
 
     fn seat_state(&mut self) -> &mut SeatState {
@@ -23,17 +132,63 @@ impl SeatHandler for AppData {
         &mut self,
         _conn: &Connection,
         qh: &QueueHandle<Self>,
-        seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
-        capability: smithay_client_toolkit::seat::Capability,
+        seat: WlSeat,
+        capability: Capability,
     ) {
-        if capability == Capability::Keyboard && self.wayland.keyboard.is_none() {
-            tracing::trace!("Adding keyboard!");
-            self.wayland.keyboard = Some(
-                self.wayland
+        match capability {
+            Capability::Keyboard if self.seat_mut(&seat).keyboard.is_none() => {
+                tracing::trace!("Adding keyboard!");
+                let keyboard = self
+                    .wayland
                     .seat_state
                     .get_keyboard(qh, &seat, None)
-                    .expect("Failed to create keyboard!"),
-            )
+                    .expect("Failed to create keyboard!");
+                let data_device = self
+                    .wayland
+                    .data_device_manager
+                    .as_ref()
+                    .map(|manager| manager.get_data_device(qh, &seat));
+                let text_input = self
+                    .wayland
+                    .text_input_manager
+                    .as_ref()
+                    .map(|manager| TextInput::new(manager.get_text_input(&seat, qh, ())));
+                let had_keyboard = self.has_keyboard();
+                let state = self.lock_data.keyboard.new_state();
+                let entry = self.seat_mut(&seat);
+                entry.keyboard = Some(keyboard);
+                entry.keyboard_state = Some(state);
+                entry.data_device = data_device;
+                entry.text_input = text_input;
+                // An on-screen keypad may go away now.
+                if !had_keyboard {
+                    self.render_all();
+                }
+            }
+            Capability::Pointer if self.seat_mut(&seat).pointer.is_none() => {
+                tracing::trace!("Adding pointer!");
+                let pointer = self
+                    .wayland
+                    .seat_state
+                    .get_pointer(qh, &seat)
+                    .expect("Failed to create pointer!");
+                let shape_device = self
+                    .wayland
+                    .cursor_shape_manager
+                    .as_ref()
+                    .map(|manager| manager.get_shape_device(&pointer, qh));
+                self.seat_mut(&seat).pointer = Some(Pointer::new(pointer, shape_device));
+            }
+            Capability::Touch if self.seat_mut(&seat).touch.is_none() => {
+                tracing::trace!("Adding touch!");
+                let touch = self
+                    .wayland
+                    .seat_state
+                    .get_touch(qh, &seat)
+                    .expect("Failed to create touch!");
+                self.seat_mut(&seat).touch = Some(touch);
+            }
+            _ => {}
         }
     }
 
@@ -41,20 +196,42 @@ impl SeatHandler for AppData {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
-        capability: smithay_client_toolkit::seat::Capability,
+        seat: WlSeat,
+        capability: Capability,
     ) {
-        if capability == Capability::Keyboard && self.wayland.keyboard.is_some() {
-            self.wayland.keyboard.take().unwrap().release();
+        match capability {
+            Capability::Keyboard => {
+                if let Some(keyboard) = self.seat_mut(&seat).keyboard.clone() {
+                    tracing::trace!("Removing keyboard!");
+                    self.stop_key_repeat_from(&keyboard);
+                    self.seat_mut(&seat).release_keyboard();
+                    if !self.has_keyboard() {
+                        self.render_all();
+                    }
+                }
+            }
+            Capability::Pointer => self.seat_mut(&seat).release_pointer(),
+            Capability::Touch => self.seat_mut(&seat).release_touch(),
+            _ => {}
         }
     }
 
-    fn remove_seat(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat,
-    ) {
+    fn remove_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, seat: WlSeat) {
+        tracing::debug!("Seat removed!");
+        let Some(index) = self.wayland.seats.iter().position(|s| s.seat == seat) else {
+            return;
+        };
+        let mut removed = self.wayland.seats.remove(index);
+        if let Some(keyboard) = &removed.keyboard {
+            self.stop_key_repeat_from(keyboard);
+        }
+        let had_keyboard = removed.keyboard.is_some();
+        removed.release_keyboard();
+        removed.release_pointer();
+        removed.release_touch();
+        if had_keyboard && !self.has_keyboard() {
+            self.render_all();
+        }
     }
 }
 
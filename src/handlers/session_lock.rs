@@ -1,4 +1,4 @@
-use std::{path::PathBuf, ptr::NonNull};
+use std::ptr::NonNull;
 
 use futures::executor;
 use raw_window_handle::{WaylandDisplayHandle, WaylandWindowHandle};
@@ -14,9 +14,24 @@ use crate::{graphics::surface::LockSurfaceWrapper, media::Media, AppData};
 impl SessionLockHandler for AppData {
     fn locked(&mut self, conn: &Connection, qh: &QueueHandle<Self>, session_lock: SessionLock) {
         tracing::trace!("Locked");
+        self.lock_data.start_grace();
+        self.transition_in();
+        if let Media::Image(ref mut im) = self.lock_data.media {
+            let largest = self
+                .wayland
+                .output_state
+                .outputs()
+                .filter_map(|output| self.wayland.output_state.info(&output))
+                .filter_map(|info| info.modes.iter().find(|m| m.current).map(|m| m.dimensions))
+                .fold((0, 0), |acc, (w, h)| {
+                    (acc.0.max(w as u32), acc.1.max(h as u32))
+                });
+            im.fit_within(largest);
+        }
         for output in self.wayland.output_state.outputs() {
             let output_info = self.wayland.output_state.info(&output).unwrap();
             let size = output_info.logical_size.unwrap();
+            let scale = output_info.scale_factor.max(1) as u32;
             let surface = self.wayland.compositor_state.create_surface(qh);
             let lock_surface = session_lock.create_lock_surface(surface, &output, qh);
 
@@ -29,7 +44,12 @@ impl SessionLockHandler for AppData {
                     NonNull::new(lock_surface.wl_surface().id().as_ptr() as *mut _).unwrap(),
                 ));
 
-            let mut gsurface = LockSurfaceWrapper::new(lock_surface, Media::from_config(&self.xdg));
+            let mut gsurface = LockSurfaceWrapper::new(
+                lock_surface,
+                self.lock_data.media.clone(),
+                output_info.name.clone(),
+            );
+            gsurface.set_scale_factor(scale);
             let surface_target = wgpu::SurfaceTargetUnsafe::RawHandle {
                 raw_window_handle,
                 raw_display_handle,
@@ -45,10 +65,12 @@ impl SessionLockHandler for AppData {
             gsurface.init(
                 &self.graphics_context,
                 vsurface,
-                (size.0 as u32, size.1 as u32),
+                (size.0 as u32 * scale, size.1 as u32 * scale),
             );
             self.lock_data.add_surface(gsurface);
         }
+        self.start_fingerprint();
+        self.wake();
     }
 
     fn finished(
@@ -70,32 +92,12 @@ impl SessionLockHandler for AppData {
         _serial: u32,
     ) {
         tracing::trace!("Configure Event");
-        let (width, height) = configure.new_size;
-        let surface = self.lock_data.session_lock_surfaces.get_mut(0).unwrap();
-        surface.resize(&self.graphics_context, (width, height));
-        match self.lock_data.media {
-            Media::Solid(color) => {
-                self.graphics_context.render_color(surface, color).unwrap();
-            }
-            Media::Image(ref mut im) => {
-                im.resize(width, height);
-                self.graphics_context
-                    .create_texture_from_image_for_surface(surface, im);
-                self.graphics_context
-                    .render_texture_for_image(surface)
-                    .unwrap();
-            }
-            Media::Shader(ref path) => {
-                self.graphics_context
-                    .create_texture_from_shader_for_surface(surface, &PathBuf::from(path));
-                self.graphics_context
-                    .render_texture_for_shader(surface)
-                    .unwrap();
-            }
-            _ => {
-                tracing::trace!("Screenshot, Video not supported yet!");
-            }
-        }
+        let Some(index) = self.surface_index(session_lock_surface.wl_surface()) else {
+            tracing::warn!("Configure for unknown lock surface");
+            return;
+        };
+        self.configure_surface(index, configure.new_size);
+        self.render_surface(index);
 
         session_lock_surface.wl_surface().commit();
     }
@@ -0,0 +1,90 @@
+use smithay_client_toolkit::reexports::{
+    client::{delegate_noop, Connection, Dispatch, QueueHandle},
+    protocols::wp::text_input::zv3::client::{
+        zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        zwp_text_input_v3::{self, ContentHint, ContentPurpose, ZwpTextInputV3},
+    },
+};
+
+use crate::app::AppData;
+use crate::lock::AuthState;
+
+/// An input method's connection to the password field. Text it commits is
+/// typed into the password once the compositor sends `done`.
+pub struct TextInput {
+    pub input: ZwpTextInputV3,
+    commit: Option<String>,
+    preedit: bool,
+}
+
+impl TextInput {
+    pub fn new(input: ZwpTextInputV3) -> Self {
+        Self {
+            input,
+            commit: None,
+            preedit: false,
+        }
+    }
+}
+
+impl Dispatch<ZwpTextInputV3, ()> for AppData {
+    fn event(
+        state: &mut Self,
+        input: &ZwpTextInputV3,
+        event: zwp_text_input_v3::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // Only our own surfaces take text; a stray enter could leave the
+        // input method enabled where the user doesn't expect it.
+        if let zwp_text_input_v3::Event::Enter { surface } = &event {
+            if state.surface_index(surface).is_none() {
+                return;
+            }
+        }
+        let Some(text_input) = state
+            .wayland
+            .seats
+            .iter_mut()
+            .filter_map(|s| s.text_input.as_mut())
+            .find(|t| t.input == *input)
+        else {
+            return;
+        };
+        match event {
+            zwp_text_input_v3::Event::Enter { .. } => {
+                input.enable();
+                // Asks the input method not to show or learn what is typed.
+                input.set_content_type(
+                    ContentHint::HiddenText | ContentHint::SensitiveData,
+                    ContentPurpose::Password,
+                );
+                input.commit();
+            }
+            zwp_text_input_v3::Event::Leave { .. } => {
+                input.disable();
+                input.commit();
+            }
+            // Preedit text is never drawn, it only shows that something is
+            // being typed.
+            zwp_text_input_v3::Event::PreeditString { text, .. } => {
+                text_input.preedit = text.is_some_and(|text| !text.is_empty());
+            }
+            zwp_text_input_v3::Event::CommitString { text } => text_input.commit = text,
+            zwp_text_input_v3::Event::Done { .. } => {
+                let preedit = text_input.preedit;
+                match text_input.commit.take() {
+                    Some(text) => state.type_text(&text),
+                    None if preedit => state.set_auth_state(AuthState::Typing),
+                    None => {}
+                }
+            }
+            // No surrounding text is ever sent, so there is nothing to
+            // delete around the cursor.
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(AppData: ignore ZwpTextInputManagerV3);
@@ -0,0 +1,91 @@
+use smithay_client_toolkit::{
+    reexports::client::{
+        protocol::{wl_surface::WlSurface, wl_touch::WlTouch},
+        Connection, QueueHandle,
+    },
+    seat::touch::TouchHandler,
+};
+
+use crate::app::AppData;
+
+// Taps act like clicks where they land. Moving a finger off a key doesn't
+// cancel it, since the action already ran on touch down.
+impl TouchHandler for AppData {
+    fn down(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _serial: u32,
+        _time: u32,
+        surface: WlSurface,
+        _id: i32,
+        position: (f64, f64),
+    ) {
+        self.wake();
+        if self.grace_unlock(true) {
+            return;
+        }
+        let action = self.target(&surface, position);
+        if !self.lock_data.touched {
+            // The first touch may bring up the keypad. It only goes through
+            // if that left the same thing under the finger, so it doesn't
+            // press a key the user never saw.
+            self.lock_data.touched = true;
+            self.render_all();
+            if self.target(&surface, position) != action {
+                return;
+            }
+        }
+        if let Some(action) = action {
+            self.run_action(action);
+        }
+    }
+
+    fn up(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _serial: u32,
+        _time: u32,
+        _id: i32,
+    ) {
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _time: u32,
+        _id: i32,
+        _position: (f64, f64),
+    ) {
+    }
+
+    fn shape(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _major: f64,
+        _minor: f64,
+    ) {
+    }
+
+    fn orientation(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _orientation: f64,
+    ) {
+    }
+
+    fn cancel(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _touch: &WlTouch) {}
+}
+
+smithay_client_toolkit::delegate_touch!(AppData);
@@ -0,0 +1,210 @@
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::reexports::{
+    calloop::{
+        timer::{TimeoutAction, Timer},
+        RegistrationToken,
+    },
+    protocols_wlr::output_power_management::v1::client::zwlr_output_power_v1::{
+        Mode, ZwlrOutputPowerV1,
+    },
+};
+
+use crate::app::AppData;
+use crate::config::Config;
+
+const SECTION: &str = "idle";
+/// Both stages are off unless configured.
+const DIM_DEFAULT: u64 = 0;
+const OFF_DEFAULT: u64 = 0;
+const AFTER_FAILURE_DEFAULT: u64 = 0;
+const BRIGHTNESS_DEFAULT: f32 = 0.3;
+const FADE_DEFAULT: f32 = 1.0;
+/// Time between frames while fading down.
+const FADE_STEP: Duration = Duration::from_millis(16);
+
+fn seconds(conf: &Config, key: &str, default: u64) -> Option<Duration> {
+    let seconds = conf
+        .get(SECTION, key)
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(default);
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// Dims the lock screen and then turns the outputs off when nobody touches
+/// it for a while. Times count from the last input.
+pub struct Idle {
+    dim_after: Option<Duration>,
+    off_after: Option<Duration>,
+    /// Replaces `dim_after` once an attempt failed, until the next input.
+    failed_after: Option<Duration>,
+    /// How dark dimming goes, as the share of darkness laid over the frame.
+    depth: f32,
+    fade: Duration,
+    since: Instant,
+    failed: bool,
+    /// Darkness laid over every frame right now.
+    pub dim: f32,
+    timer: Option<RegistrationToken>,
+    /// Outputs turned off, to turn back on when woken.
+    powered_off: Vec<ZwlrOutputPowerV1>,
+}
+
+impl Idle {
+    /// Reads `[idle]`: `dim` and `off` are seconds without input before
+    /// each stage, `0` skipping it, and `after_failure` shortens the wait
+    /// for dimming right after a failed attempt.
+    pub fn from_config(conf: &Config) -> Self {
+        let brightness = conf
+            .get(SECTION, "brightness")
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(BRIGHTNESS_DEFAULT)
+            .clamp(0.0, 1.0);
+        let fade = conf
+            .get(SECTION, "fade")
+            .and_then(|v| v.parse::<f32>().ok())
+            .filter(|f| f.is_finite() && *f >= 0.0)
+            .unwrap_or(FADE_DEFAULT);
+        Self {
+            dim_after: seconds(conf, "dim", DIM_DEFAULT),
+            off_after: seconds(conf, "off", OFF_DEFAULT),
+            failed_after: seconds(conf, "after_failure", AFTER_FAILURE_DEFAULT),
+            depth: 1.0 - brightness,
+            fade: Duration::from_secs_f32(fade),
+            since: Instant::now(),
+            failed: false,
+            dim: 0.0,
+            timer: None,
+            powered_off: Vec::new(),
+        }
+    }
+
+    pub fn turns_off(&self) -> bool {
+        self.off_after.is_some()
+    }
+
+    /// When dimming and turning off start, counted from the last input.
+    /// After a failure both come earlier by the same amount, keeping the
+    /// gap between them.
+    fn stages(&self) -> (Option<Duration>, Option<Duration>) {
+        let Some(failed_after) = self.failed_after.filter(|_| self.failed) else {
+            return (self.dim_after, self.off_after);
+        };
+        match self.dim_after {
+            Some(dim) => (
+                Some(failed_after.min(dim)),
+                self.off_after
+                    .map(|off| off.saturating_sub(dim.saturating_sub(failed_after))),
+            ),
+            None => (None, self.off_after.map(|off| off.min(failed_after))),
+        }
+    }
+}
+
+impl AppData {
+    /// Restarts the idle clock on any input, undoing dimming and turning
+    /// outputs back on.
+    pub fn wake(&mut self) {
+        self.restart_idle(false);
+    }
+
+    /// Restarts the idle clock with the shorter wait used after a failed
+    /// attempt.
+    pub fn idle_after_failure(&mut self) {
+        self.restart_idle(true);
+    }
+
+    fn restart_idle(&mut self, failed: bool) {
+        if failed {
+            // Dimming may now be due before the armed timer fires.
+            if let Some(token) = self.lock_data.idle.timer.take() {
+                self.wayland.loop_handle.remove(token);
+            }
+        }
+        let idle = &mut self.lock_data.idle;
+        idle.since = Instant::now();
+        idle.failed = failed;
+        for power in idle.powered_off.drain(..) {
+            power.set_mode(Mode::On);
+            power.destroy();
+        }
+        let dimmed = idle.dim > 0.0;
+        idle.dim = 0.0;
+        let enabled = idle.dim_after.is_some() || idle.off_after.is_some();
+        if enabled && idle.timer.is_none() {
+            // Input comes in far more often than the timer fires, so it
+            // stays armed and works out on its own what is due.
+            let token = self
+                .wayland
+                .loop_handle
+                .insert_source(Timer::immediate(), |_, _, app_data| app_data.idle_step())
+                .expect("Failed to insert the idle timer!");
+            self.lock_data.idle.timer = Some(token);
+        }
+        if dimmed {
+            self.render_all();
+        }
+    }
+
+    /// Moves on to whatever stage is due and says when to check again.
+    fn idle_step(&mut self) -> TimeoutAction {
+        let idle = &self.lock_data.idle;
+        let elapsed = idle.since.elapsed();
+        let (dim_at, off_at) = idle.stages();
+        let mut next = None;
+
+        if let Some(dim_at) = dim_at {
+            if elapsed < dim_at {
+                next = Some(dim_at - elapsed);
+            } else if idle.dim < idle.depth {
+                let progress = if idle.fade.is_zero() {
+                    1.0
+                } else {
+                    (elapsed - dim_at).as_secs_f32() / idle.fade.as_secs_f32()
+                };
+                let dim = idle.depth * progress.min(1.0);
+                self.lock_data.idle.dim = dim;
+                self.render_all();
+                if dim < self.lock_data.idle.depth {
+                    next = Some(FADE_STEP);
+                }
+            }
+        }
+
+        if let Some(off_at) = off_at {
+            if elapsed < off_at {
+                let wait = off_at - elapsed;
+                next = Some(next.map_or(wait, |next: Duration| next.min(wait)));
+            } else if self.lock_data.idle.powered_off.is_empty() {
+                self.power_off();
+            }
+        }
+
+        match next {
+            Some(next) => TimeoutAction::ToDuration(next),
+            None => {
+                self.lock_data.idle.timer = None;
+                TimeoutAction::Drop
+            }
+        }
+    }
+
+    fn power_off(&mut self) {
+        let Some(manager) = self.wayland.output_power_manager.as_ref() else {
+            return;
+        };
+        tracing::debug!("Turning outputs off");
+        let qh = &self.wayland.queue_handle;
+        let powered_off: Vec<_> = self
+            .wayland
+            .output_state
+            .outputs()
+            .map(|output| {
+                let power = manager.get_output_power(&output, qh, ());
+                power.set_mode(Mode::Off);
+                power
+            })
+            .collect();
+        self.lock_data.idle.powered_off = powered_off;
+    }
+}
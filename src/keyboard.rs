@@ -0,0 +1,301 @@
+use std::{ffi::OsString, time::Duration};
+
+use smithay_client_toolkit::seat::keyboard::{KeyEvent, Keysym, Modifiers, RepeatInfo};
+use xkbcommon::xkb;
+
+use crate::config::Config;
+
+const SECTION: &str = "keyboard";
+/// Used until the compositor sends its own, which `wl_keyboard` before
+/// version 4 never does.
+const REPEAT_DELAY_DEFAULT: Duration = Duration::from_millis(600);
+const REPEAT_RATE_DEFAULT: u32 = 25;
+
+/// Short layout names such as `us` or `de`, in layout order, from the
+/// `xkb_symbols "pc+us+de:2+inet(evdev)"` line of a keymap.
+fn short_layout_names(keymap: &str) -> Vec<(u32, String)> {
+    let Some(start) = keymap.find("xkb_symbols \"") else {
+        return Vec::new();
+    };
+    let rest = &keymap[start + "xkb_symbols \"".len()..];
+    let Some(end) = rest.find('"') else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    for (position, part) in rest[..end].split('+').enumerate() {
+        let (name, index) = match part.split_once(':') {
+            Some((name, index)) => match index.parse::<u32>() {
+                Ok(index) => (name, index.saturating_sub(1)),
+                Err(_) => continue,
+            },
+            // The first layout comes right after the model and has no index;
+            // later parts without one are options such as `inet(evdev)`.
+            None if position == 1 => (part, 0),
+            None => continue,
+        };
+        names.push((index, name.to_string()));
+    }
+    names
+}
+
+/// What a key press amounts to once compose sequences and dead keys are
+/// taken into account.
+pub enum Composed {
+    /// The key isn't part of a sequence.
+    Key(Keysym),
+    /// The key started, continued or cancelled a sequence.
+    Pending,
+    /// The key finished a sequence producing this text.
+    Text(String),
+}
+
+/// The locale compose sequences are looked up for, as libc picks it.
+fn locale() -> OsString {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| std::env::var_os(name).filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "C".into())
+}
+
+/// Keyboard settings from the config, shared by the keyboards of every
+/// seat.
+pub struct KeyboardConfig {
+    context: xkb::Context,
+    compose: Option<xkb::compose::Table>,
+    cycle_key: Option<Keysym>,
+    /// Whether Ctrl+V pastes the clipboard into the password field.
+    pub allow_paste: bool,
+}
+
+impl KeyboardConfig {
+    pub fn from_config(conf: &Config) -> Self {
+        let cycle_key = conf.get(SECTION, "cycle_layout_key").and_then(|name| {
+            let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_NO_FLAGS);
+            if keysym == Keysym::NoSymbol {
+                tracing::warn!("Unknown keysym '{}' for [keyboard] cycle_layout_key", name);
+                None
+            } else {
+                Some(keysym)
+            }
+        });
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let compose = match xkb::compose::Table::new_from_locale(
+            &context,
+            &locale(),
+            xkb::compose::COMPILE_NO_FLAGS,
+        ) {
+            Ok(table) => Some(table),
+            Err(_) => {
+                tracing::warn!("No compose table for {:?}, dead keys won't work", locale());
+                None
+            }
+        };
+        Self {
+            context,
+            compose,
+            cycle_key,
+            allow_paste: conf
+                .get(SECTION, "allow_paste")
+                .and_then(|v| v.parse::<bool>().ok())
+                .unwrap_or(false),
+        }
+    }
+
+    pub fn is_cycle_key(&self, keysym: Keysym) -> bool {
+        self.cycle_key == Some(keysym)
+    }
+
+    /// State for a keyboard that just showed up, until the compositor
+    /// sends its keymap.
+    pub fn new_state(&self) -> KeyboardState {
+        KeyboardState {
+            context: self.context.clone(),
+            keymap: None,
+            state: None,
+            compose: self
+                .compose
+                .as_ref()
+                .map(|table| xkb::compose::State::new(table, xkb::compose::STATE_NO_FLAGS)),
+            short_names: Vec::new(),
+            caps_lock: false,
+            num_lock: false,
+            ctrl: false,
+            layout: 0,
+            layout_override: None,
+            repeat: Some((
+                REPEAT_DELAY_DEFAULT,
+                Duration::from_secs(1) / REPEAT_RATE_DEFAULT,
+            )),
+        }
+    }
+}
+
+/// Modifier and layout state of one keyboard, mirrored from the
+/// compositor's xkb state. The lock screen can also switch to another
+/// layout of the keymap on its own, in which case keys are translated here
+/// with that layout.
+pub struct KeyboardState {
+    context: xkb::Context,
+    keymap: Option<xkb::Keymap>,
+    state: Option<xkb::State>,
+    compose: Option<xkb::compose::State>,
+    short_names: Vec<(u32, String)>,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub ctrl: bool,
+    /// Layout the compositor reports as active.
+    layout: u32,
+    /// Layout picked on the lock screen, until the compositor switches.
+    layout_override: Option<u32>,
+    /// Delay before a held key repeats and the time between repeats, or
+    /// `None` when the compositor turned repeating off.
+    repeat: Option<(Duration, Duration)>,
+}
+
+impl KeyboardState {
+    pub fn set_repeat_info(&mut self, info: RepeatInfo) {
+        self.repeat = match info {
+            RepeatInfo::Repeat { rate, delay } => Some((
+                Duration::from_millis(delay as u64),
+                Duration::from_secs(1) / rate.get(),
+            )),
+            RepeatInfo::Disable => None,
+        };
+    }
+
+    /// Delay and interval for repeating `event` while it is held, if the
+    /// keymap says that key repeats at all.
+    pub fn repeat(&self, event: &KeyEvent) -> Option<(Duration, Duration)> {
+        let repeats = self
+            .keymap
+            .as_ref()
+            .is_none_or(|keymap| keymap.key_repeats(xkb::Keycode::new(event.raw_code + 8)));
+        self.repeat.filter(|_| repeats)
+    }
+
+    pub fn set_keymap(&mut self, keymap: String) {
+        self.short_names = short_layout_names(&keymap);
+        self.keymap = xkb::Keymap::new_from_string(
+            &self.context,
+            keymap,
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        );
+        if self.keymap.is_none() {
+            tracing::warn!("Unable to compile the compositor's keymap");
+        }
+        self.state = self.keymap.as_ref().map(xkb::State::new);
+        self.layout_override = None;
+    }
+
+    /// Takes the compositor's modifiers and layout. Returns whether anything
+    /// shown on the lock screen changed.
+    pub fn set_modifiers(&mut self, modifiers: &Modifiers, layout: u32) -> bool {
+        let changed = self.caps_lock != modifiers.caps_lock
+            || self.num_lock != modifiers.num_lock
+            || self.layout != layout;
+        if self.layout != layout {
+            // A switch made through the compositor wins over ours.
+            self.layout_override = None;
+        }
+        self.ctrl = modifiers.ctrl;
+        self.caps_lock = modifiers.caps_lock;
+        self.num_lock = modifiers.num_lock;
+        self.layout = layout;
+        changed
+    }
+
+    pub fn num_layouts(&self) -> u32 {
+        self.keymap
+            .as_ref()
+            .map_or(0, |keymap| keymap.num_layouts())
+    }
+
+    pub fn active_layout(&self) -> u32 {
+        self.layout_override.unwrap_or(self.layout)
+    }
+
+    /// Short name of the active layout such as `de`, or xkb's descriptive
+    /// name when the keymap doesn't say.
+    pub fn layout_name(&self) -> Option<String> {
+        let layout = self.active_layout();
+        self.short_names
+            .iter()
+            .find(|(index, _)| *index == layout)
+            .map(|(_, name)| name.clone())
+            .or_else(|| {
+                let keymap = self.keymap.as_ref()?;
+                (layout < keymap.num_layouts()).then(|| keymap.layout_get_name(layout).to_string())
+            })
+    }
+
+    /// Switches the lock screen to the next layout of the keymap.
+    pub fn cycle_layout(&mut self) {
+        let count = self.num_layouts();
+        if count > 1 {
+            self.layout_override = Some((self.active_layout() + 1) % count);
+        }
+    }
+
+    /// Runs `keysym` through the compose table.
+    pub fn compose(&mut self, keysym: Keysym) -> Composed {
+        let Some(compose) = self.compose.as_mut() else {
+            return Composed::Key(keysym);
+        };
+        if compose.feed(keysym) == xkb::compose::FeedResult::Ignored {
+            return Composed::Key(keysym);
+        }
+        match compose.status() {
+            xkb::compose::Status::Nothing => Composed::Key(keysym),
+            xkb::compose::Status::Composing | xkb::compose::Status::Cancelled => Composed::Pending,
+            xkb::compose::Status::Composed => {
+                let composed = match compose.utf8() {
+                    Some(text) => Composed::Text(text),
+                    None => compose.keysym().map_or(Composed::Pending, Composed::Key),
+                };
+                compose.reset();
+                composed
+            }
+        }
+    }
+
+    /// Drops a half-typed compose sequence.
+    pub fn reset_compose(&mut self) {
+        if let Some(compose) = self.compose.as_mut() {
+            compose.reset();
+        }
+    }
+
+    /// Keeps the local state's held modifiers in step with the keyboard.
+    pub fn update_key(&mut self, event: &KeyEvent, pressed: bool) {
+        if let Some(state) = self.state.as_mut() {
+            let direction = if pressed {
+                xkb::KeyDirection::Down
+            } else {
+                xkb::KeyDirection::Up
+            };
+            state.update_key(xkb::Keycode::new(event.raw_code + 8), direction);
+        }
+    }
+
+    /// The keysym for `event` in the active layout. This is the
+    /// compositor's unless a layout was picked on the lock screen.
+    pub fn keysym(&mut self, event: &KeyEvent) -> Keysym {
+        let Some(layout) = self.layout_override.filter(|l| *l != self.layout) else {
+            return event.keysym;
+        };
+        let (Some(keymap), Some(state)) = (self.keymap.as_ref(), self.state.as_mut()) else {
+            return event.keysym;
+        };
+        let mut locked = 0;
+        if self.caps_lock {
+            locked |= 1 << keymap.mod_get_index(xkb::MOD_NAME_CAPS);
+        }
+        if self.num_lock {
+            locked |= 1 << keymap.mod_get_index(xkb::MOD_NAME_NUM);
+        }
+        let depressed = state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        state.update_mask(depressed, 0, locked, 0, 0, layout);
+        state.key_get_one_sym(xkb::Keycode::new(event.raw_code + 8))
+    }
+}
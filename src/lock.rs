@@ -1,45 +1,219 @@
-use smithay_client_toolkit::session_lock::SessionLock;
+use std::time::{Duration, Instant};
 
+use smithay_client_toolkit::{
+    reexports::{calloop::RegistrationToken, client::protocol::wl_keyboard::WlKeyboard},
+    session_lock::SessionLock,
+};
+
+use crate::config::Config;
+use crate::fprint::Fprint;
 use crate::graphics::surface::LockSurfaceWrapper;
+use crate::idle::Idle;
+use crate::keyboard::KeyboardConfig;
 use crate::media::Media;
 use crate::pam::auth;
+use crate::transition::Transition;
+use crate::widgets::{layout::Layout, message};
+
+const AUTH_SERVICE_DEFAULT: &str = "system-auth";
+const AUTH_MAX_ATTEMPTS_DEFAULT: usize = 0;
+const AUTH_LOCKOUT_DEFAULT: u64 = 30;
+const AUTH_GRACE_DEFAULT: u64 = 0;
+const FINGERPRINT_FINGER_DEFAULT: &str = "any";
+const FINGERPRINT_PROMPT_DEFAULT: &str = "Place your finger on the reader";
+
+/// What the password indicator shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthState {
+    Idle,
+    Typing,
+    Clearing,
+    Verifying,
+    Wrong,
+}
+
+impl AuthState {
+    /// How long the state is shown before falling back to `Idle`.
+    pub fn timeout(&self) -> Option<Duration> {
+        match self {
+            AuthState::Typing | AuthState::Clearing => Some(Duration::from_secs(1)),
+            AuthState::Wrong => Some(Duration::from_secs(2)),
+            AuthState::Idle | AuthState::Verifying => None,
+        }
+    }
+}
 
 pub struct LockState {
     session_lock: SessionLock,
     retries: usize,
+    service: String,
+    max_attempts: usize,
+    lockout: Duration,
+    locked_out_until: Option<Instant>,
+    /// How long after locking input unlocks without a password, if at all.
+    grace: Option<Duration>,
+    grace_pointer: bool,
+    grace_until: Option<Instant>,
     pub session_lock_surfaces: Vec<LockSurfaceWrapper>,
     pub password_buffer: String,
+    /// Whether the password is shown in clear text.
+    pub reveal: bool,
+    pub touched: bool,
+    pub keypad_shift: bool,
     pub media: Media,
+    pub layout: Layout,
+    /// Lines from the last authentication attempt shown under the message.
+    pub feedback: Vec<String>,
+    pub feedback_timeout: Option<Duration>,
+    pub feedback_timer: Option<RegistrationToken>,
+    pub auth_state: AuthState,
+    pub auth_state_timer: Option<RegistrationToken>,
+    /// Settings shared by every keyboard; their state is kept per seat.
+    pub keyboard: KeyboardConfig,
+    /// The keyboard and raw code of the key being repeated, and the timer
+    /// repeating it.
+    pub key_repeat: Option<(WlKeyboard, u32, RegistrationToken)>,
+    /// Finger fprintd verifies, or `None` when fingerprint unlock is off.
+    pub fingerprint_finger: Option<String>,
+    pub fingerprint_prompt: String,
+    /// Set while the reader is claimed and waiting for a finger.
+    pub fingerprint: Option<Fprint>,
+    pub idle: Idle,
+    pub transition: Transition,
 }
 
 impl LockState {
-    pub fn from_lock(session_lock: SessionLock, media: Media) -> Self {
+    /// Sets up the lock from `conf`. `idle` is set when an idle daemon
+    /// started it, and `[auth] grace_manual=false` keeps the grace period to
+    /// those locks.
+    pub fn from_lock(session_lock: SessionLock, media: Media, conf: &Config, idle: bool) -> Self {
+        let grace_manual = conf
+            .get("auth", "grace_manual")
+            .and_then(|v| v.parse::<bool>().ok())
+            .unwrap_or(true);
+        let grace = conf
+            .get("auth", "grace")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(AUTH_GRACE_DEFAULT);
         Self {
             session_lock,
             retries: 0,
+            service: conf
+                .get("auth", "service")
+                .unwrap_or(AUTH_SERVICE_DEFAULT)
+                .to_string(),
+            max_attempts: conf
+                .get("auth", "max_attempts")
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(AUTH_MAX_ATTEMPTS_DEFAULT),
+            lockout: Duration::from_secs(
+                conf.get("auth", "lockout")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(AUTH_LOCKOUT_DEFAULT),
+            ),
+            locked_out_until: None,
+            grace: (grace > 0 && (idle || grace_manual)).then(|| Duration::from_secs(grace)),
+            grace_pointer: conf
+                .get("auth", "grace_pointer")
+                .and_then(|v| v.parse::<bool>().ok())
+                .unwrap_or(true),
+            grace_until: None,
             password_buffer: String::new(),
+            reveal: false,
+            touched: false,
+            keypad_shift: false,
             session_lock_surfaces: Vec::new(),
             media,
+            layout: Layout::from_config(conf),
+            feedback: Vec::new(),
+            feedback_timeout: message::feedback_timeout(conf),
+            feedback_timer: None,
+            auth_state: AuthState::Idle,
+            auth_state_timer: None,
+            keyboard: KeyboardConfig::from_config(conf),
+            key_repeat: None,
+            fingerprint_finger: conf
+                .get("fingerprint", "enabled")
+                .and_then(|v| v.parse::<bool>().ok())
+                .unwrap_or(false)
+                .then(|| {
+                    conf.get("fingerprint", "finger")
+                        .unwrap_or(FINGERPRINT_FINGER_DEFAULT)
+                        .to_string()
+                }),
+            fingerprint_prompt: conf
+                .get("fingerprint", "prompt")
+                .unwrap_or(FINGERPRINT_PROMPT_DEFAULT)
+                .to_string(),
+            fingerprint: None,
+            idle: Idle::from_config(conf),
+            transition: Transition::from_config(conf),
         }
     }
 
+    /// Deletes back to the start of the previous word, like readline's
+    /// Ctrl+W.
+    pub fn delete_word(&mut self) {
+        let trimmed = self.password_buffer.trim_end_matches(char::is_whitespace);
+        let start = trimmed
+            .rfind(char::is_whitespace)
+            .map_or(0, |i| i + trimmed[i..].chars().next().unwrap().len_utf8());
+        self.password_buffer.truncate(start);
+    }
+
     pub fn add_surface(&mut self, session_lock_surface: LockSurfaceWrapper) {
         self.session_lock_surfaces.push(session_lock_surface);
     }
 
+    /// Opens the grace period, from the moment the session is locked.
+    pub fn start_grace(&mut self) {
+        self.grace_until = self.grace.map(|grace| Instant::now() + grace);
+    }
+
+    /// Whether input now unlocks without a password. `pointer` is set for
+    /// pointer and touch input, which `grace_pointer=false` leaves out.
+    pub fn in_grace(&self, pointer: bool) -> bool {
+        (!pointer || self.grace_pointer)
+            && self.grace_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Ends the lock. Only to be called once the user has authenticated.
+    pub fn unlock(&mut self) {
+        // Lets go of the reader before the session gets it back.
+        self.fingerprint = None;
+        self.session_lock.unlock();
+    }
+
+    /// Checks the typed password, leaving the unlock itself to the caller.
+    /// PAM's own info and error text replaces `feedback`.
     pub fn unlock_with_auth(&mut self) -> Result<(), &str> {
+        self.feedback.clear();
+        if let Some(until) = self.locked_out_until {
+            if Instant::now() < until {
+                self.password_buffer.clear();
+                return Err("Too many failed attempts, try again later");
+            }
+            self.locked_out_until = None;
+        }
         match auth(
-            String::from("system-auth"),
+            self.service.clone(),
             whoami::username(),
             self.password_buffer.clone(),
+            &mut self.feedback,
         ) {
-            Ok(_) => {
-                self.session_lock.unlock();
-                Ok(())
-            }
+            Ok(_) => Ok(()),
             Err(_) => {
                 self.password_buffer.clear();
-                self.retries += self.retries;
+                self.retries += 1;
+                if self.max_attempts > 0 && self.retries >= self.max_attempts {
+                    tracing::warn!(
+                        "{} failed attempts, locking out for {:?}",
+                        self.retries,
+                        self.lockout
+                    );
+                    self.retries = 0;
+                    self.locked_out_until = Some(Instant::now() + self.lockout);
+                }
                 Err("Authentication Error!")
             }
         }
@@ -1,10 +1,18 @@
 mod app;
 mod config;
+mod fprint;
 mod graphics;
 mod handlers;
+mod idle;
+mod keyboard;
 mod lock;
 mod media;
+mod mpris;
 mod pam;
+#[cfg(test)]
+mod testing;
+mod transition;
+mod widgets;
 
 use app::AppData;
 use std::io;
@@ -20,6 +28,8 @@ fn initialize_tracing() {
 
 fn main() {
     initialize_tracing();
+    // Idle daemons pass `--idle`, telling their locks apart from manual ones.
+    let idle = std::env::args().skip(1).any(|arg| arg == "--idle");
     let xdg_dirs = xdg::BaseDirectories::with_prefix("something_lock").unwrap();
-    AppData::connect(xdg_dirs);
+    AppData::connect(xdg_dirs, idle);
 }
@@ -1,30 +1,81 @@
 use std::{path::Path, process};
 
-use image::{imageops::resize, RgbaImage};
+use image::{
+    error::{ParameterError, ParameterErrorKind},
+    imageops::{resize, FilterType},
+    DynamicImage, ImageDecoder, ImageError, ImageReader, ImageResult, RgbaImage,
+};
 
-#[derive(Debug)]
+/// Only downscale at load time once the image is this many times larger
+/// than what the largest output needs.
+const DOWNSCALE_THRESHOLD: f32 = 1.5;
+
+/// Longest side an image may have to be uploaded as a texture, the
+/// `max_texture_dimension_2d` of the downlevel WebGL2 limits the device is
+/// created with.
+pub const MAX_TEXTURE_SIZE: u32 = 2048;
+
+#[derive(Debug, Clone)]
 pub struct Image {
     pub blur: u32,
     pub buffer: RgbaImage,
 }
 
+/// Decodes the first frame of `p` and rotates it upright according to its
+/// EXIF orientation, then [`shrink`]s it for covering `max`. Images without
+/// pixels are refused, since nothing can be scaled or uploaded from them.
+pub fn decode(p: &Path, max: (u32, u32)) -> ImageResult<RgbaImage> {
+    let mut decoder = ImageReader::open(p)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    if width == 0 || height == 0 {
+        return Err(ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::Generic(format!("image is {}x{}", width, height)),
+        )));
+    }
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(shrink(image.to_rgba8(), max))
+}
+
+/// Shrinks `image`, keeping its aspect ratio, when it is much larger than
+/// needed to cover `max`, and in any case until it fits in a texture. A
+/// zero `max` leaves only the texture limit.
+pub fn shrink(image: RgbaImage, max: (u32, u32)) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut scale = MAX_TEXTURE_SIZE as f32 / width.max(height) as f32;
+    if max.0 > 0 && max.1 > 0 {
+        let cover = f32::max(max.0 as f32 / width as f32, max.1 as f32 / height as f32);
+        if cover * DOWNSCALE_THRESHOLD <= 1.0 {
+            scale = scale.min(cover);
+        }
+    }
+    if scale >= 1.0 {
+        return image;
+    }
+    let new_width = ((width as f32 * scale).ceil() as u32).clamp(1, MAX_TEXTURE_SIZE);
+    let new_height = ((height as f32 * scale).ceil() as u32).clamp(1, MAX_TEXTURE_SIZE);
+    tracing::trace!(
+        "Downscaling image from ({}, {}) to ({}, {}) to cover ({}, {})",
+        width,
+        height,
+        new_width,
+        new_height,
+        max.0,
+        max.1
+    );
+    resize(&image, new_width, new_height, FilterType::Triangle)
+}
+
 impl Image {
     pub fn init(p: &Path, blur: u32) -> Self {
         Image {
             blur,
-            buffer: match image::open(p) {
-                Ok(image) => {
-                    image.to_rgba8()
-                    // if let Some(r) = image.as_rgba8() {
-                    //     r.clone()
-                    // } else if let Some(r) = image.as_rgb8() {
-                    //     let r2: RgbaImage = r.convert();
-                    //     r2
-                    // } else {
-                    //     tracing::error!("Unable to convert to rgba8");
-                    //     process::exit(1);
-                    // }
-                }
+            // Outputs aren't known yet, fit_within shrinks it further.
+            buffer: match decode(p, (0, 0)) {
+                Ok(image) => image,
                 Err(e) => {
                     tracing::error!("Unable to open given path, {}", e);
                     process::exit(1);
@@ -33,6 +84,15 @@ impl Image {
         }
     }
 
+    /// Shrinks the buffer, keeping its aspect ratio, when it is much larger
+    /// than needed to cover an output of `max` size.
+    pub fn fit_within(&mut self, max: (u32, u32)) {
+        if max.0 == 0 || max.1 == 0 {
+            return;
+        }
+        self.buffer = shrink(std::mem::take(&mut self.buffer), max);
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         let new_buffer = {
             let buf = self.buffer.clone();
@@ -44,7 +104,7 @@ impl Image {
                     width,
                     height
                 );
-                let new_buf = resize(&buf, width, height, image::imageops::FilterType::Nearest);
+                let new_buf = resize(&buf, width, height, FilterType::Nearest);
                 tracing::trace!("Resized to : {}, {}", new_buf.width(), new_buf.height());
                 new_buf
             } else {
@@ -60,3 +120,35 @@ impl Image {
         self.buffer = new_buffer;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn keeps_images_close_to_the_size_needed() {
+        let image = shrink(RgbaImage::new(120, 100), (100, 100));
+        assert_eq!(image.dimensions(), (120, 100));
+    }
+
+    #[test]
+    fn shrinks_to_cover() {
+        let image = shrink(RgbaImage::new(1000, 500), (100, 100));
+        assert_eq!(image.dimensions(), (200, 100));
+    }
+
+    #[test]
+    fn caps_oversized_images_at_the_texture_limit() {
+        let dir = TempDir::new();
+        let path = dir.path().join("wide.png");
+        RgbaImage::new(5000, 1000).save(&path).unwrap();
+
+        assert_eq!(decode(&path, (0, 0)).unwrap().dimensions(), (2048, 410));
+        // Even when a larger size was asked for.
+        assert_eq!(
+            decode(&path, (8000, 4000)).unwrap().dimensions(),
+            (2048, 410)
+        );
+    }
+}
@@ -1,20 +1,26 @@
 pub mod image_media;
+pub mod selection;
 pub mod video_media;
 
 use image_media::Image;
+use selection::Selection;
 
+#[derive(Clone)]
 pub enum Media {
     Image(Image),
-    Shader(String),
+    Shader(PathBuf),
     Screenshot,
     Solid(wgpu::Color),
     None,
 }
 use std::path::PathBuf;
 
-use ini::Ini;
 use wgpu::Color;
 
+use crate::config::Config;
+
+const SELECTION_STATE_FILE: &str = "wallpaper";
+
 const SOLID_RED_DEFAULT_STR: &str = "255";
 const SOLID_GREEN_DEFAULT_STR: &str = "255";
 const SOLID_BLUE_DEFAULT_STR: &str = "255";
@@ -27,95 +33,123 @@ const SOLID_ALPHA_DEFAULT: u32 = 255;
 
 const IMAGE_BLUR_SIZE_DEFAULT: u32 = 4;
 const IMAGE_BLUR_SIZE_DEFAULT_STR: &str = "4";
+const IMAGE_SELECTION_DEFAULT: Selection = Selection::Random;
 
 impl Media {
-    pub fn from_config(base: &xdg::BaseDirectories) -> Self {
-        let config_file = base
-            .place_config_file("conf.ini")
-            .expect("cannot create config directory");
-        tracing::trace!("Config file path: {:?}", config_file);
-        match Ini::load_from_file(config_file.clone()) {
-            Ok(conf) => {
-                let main_section = conf.section(Some("main")).unwrap();
-                match main_section.get("type") {
-                    Some(s) => match s {
-                        "image" => {
-                            let image_section = conf.section(Some("image")).unwrap();
-                            Media::Image(Image::init(
-                                &PathBuf::from(image_section.get("path").unwrap()),
-                                image_section
-                                    .get("blur")
-                                    .unwrap_or(IMAGE_BLUR_SIZE_DEFAULT_STR)
-                                    .parse::<u32>()
-                                    .unwrap_or(IMAGE_BLUR_SIZE_DEFAULT),
-                            ))
-                        }
-                        "screenshot" => {
-                            let _screenshot_section = conf.section(Some("screenshot")).unwrap();
-                            Media::Screenshot
-                        }
-                        "solid" => {
-                            let solid_section = conf.section(Some("solid")).unwrap();
-                            let r = solid_section
-                                .get("red")
-                                .unwrap_or(SOLID_RED_DEFAULT_STR)
-                                .parse::<u32>()
-                                .unwrap_or(SOLID_RED_DEFAULT);
-                            let g = solid_section
-                                .get("green")
-                                .unwrap_or(SOLID_GREEN_DEFAULT_STR)
-                                .parse::<u32>()
-                                .unwrap_or(SOLID_GREEN_DEFAULT);
-                            let b = solid_section
-                                .get("blue")
-                                .unwrap_or(SOLID_BLUE_DEFAULT_STR)
-                                .parse::<u32>()
-                                .unwrap_or(SOLID_BLUE_DEFAULT);
-                            let a = solid_section
-                                .get("alpha")
-                                .unwrap_or(SOLID_ALPHA_DEFAULT_STR)
-                                .parse::<u32>()
-                                .unwrap_or(SOLID_ALPHA_DEFAULT);
-                            tracing::trace!(
-                                "Solid Type: Color is (rgba) ({}, {}, {}, {})",
-                                r / 255,
-                                g / 255,
-                                b / 255,
-                                a
-                            );
-                            Media::Solid(Color {
-                                r: (r / 255) as f64,
-                                g: (g / 255) as f64,
-                                b: (b / 255) as f64,
-                                a: (a / 255) as f64,
+    pub fn from_config(conf: &Config, base: &xdg::BaseDirectories) -> Self {
+        if conf.is_empty() {
+            tracing::warn!("No config found, using defaults");
+            return Media::Solid(Color {
+                r: (SOLID_RED_DEFAULT / 255) as f64,
+                g: (SOLID_GREEN_DEFAULT / 255) as f64,
+                b: (SOLID_BLUE_DEFAULT / 255) as f64,
+                a: (SOLID_ALPHA_DEFAULT / 255) as f64,
+            });
+        }
+        match conf.get("main", "type") {
+            Some(s) => match s {
+                "image" => {
+                    let image_section = conf.section("image").unwrap();
+                    let mut path = required_path(conf, "image", "path");
+                    if path.is_dir() {
+                        let selection = image_section
+                            .get("selection")
+                            .map(|s| {
+                                s.parse::<Selection>().unwrap_or_else(|_| {
+                                    tracing::warn!(
+                                        "Unknown selection '{}', expected 'random', 'sequential' or 'daily'",
+                                        s
+                                    );
+                                    IMAGE_SELECTION_DEFAULT
+                                })
                             })
-                        }
-                        "shader" => {
-                            let shader_section = conf.section(Some("shader")).unwrap();
-                            Media::Shader(shader_section.get("path").unwrap().to_string())
-                        }
-                        _ => {
-                            tracing::error!(
-                                "Type defined in config not one of 'image', 'video', 'shader'"
-                            );
-                            std::process::exit(1);
-                        }
-                    },
-                    None => {
-                        tracing::error!("Type not defined in config");
-                        std::process::exit(1);
+                            .unwrap_or(IMAGE_SELECTION_DEFAULT);
+                        // Only sequential selection remembers anything between
+                        // runs, so the others don't create the state directory.
+                        let state_file = (selection == Selection::Sequential)
+                            .then(|| base.place_state_file(SELECTION_STATE_FILE).ok())
+                            .flatten();
+                        path = match selection::pick(&path, selection, state_file.as_deref()) {
+                            Some(p) => p,
+                            None => {
+                                tracing::error!("No supported images found in {:?}", path);
+                                std::process::exit(1);
+                            }
+                        };
                     }
+                    Media::Image(Image::init(
+                        &path,
+                        image_section
+                            .get("blur")
+                            .unwrap_or(IMAGE_BLUR_SIZE_DEFAULT_STR)
+                            .parse::<u32>()
+                            .unwrap_or(IMAGE_BLUR_SIZE_DEFAULT),
+                    ))
                 }
+                "screenshot" => {
+                    let _screenshot_section = conf.section("screenshot").unwrap();
+                    Media::Screenshot
+                }
+                "solid" => {
+                    let solid_section = conf.section("solid").unwrap();
+                    let r = solid_section
+                        .get("red")
+                        .unwrap_or(SOLID_RED_DEFAULT_STR)
+                        .parse::<u32>()
+                        .unwrap_or(SOLID_RED_DEFAULT);
+                    let g = solid_section
+                        .get("green")
+                        .unwrap_or(SOLID_GREEN_DEFAULT_STR)
+                        .parse::<u32>()
+                        .unwrap_or(SOLID_GREEN_DEFAULT);
+                    let b = solid_section
+                        .get("blue")
+                        .unwrap_or(SOLID_BLUE_DEFAULT_STR)
+                        .parse::<u32>()
+                        .unwrap_or(SOLID_BLUE_DEFAULT);
+                    let a = solid_section
+                        .get("alpha")
+                        .unwrap_or(SOLID_ALPHA_DEFAULT_STR)
+                        .parse::<u32>()
+                        .unwrap_or(SOLID_ALPHA_DEFAULT);
+                    tracing::trace!(
+                        "Solid Type: Color is (rgba) ({}, {}, {}, {})",
+                        r / 255,
+                        g / 255,
+                        b / 255,
+                        a
+                    );
+                    Media::Solid(Color {
+                        r: (r / 255) as f64,
+                        g: (g / 255) as f64,
+                        b: (b / 255) as f64,
+                        a: (a / 255) as f64,
+                    })
+                }
+                "shader" => Media::Shader(required_path(conf, "shader", "path")),
+                _ => {
+                    tracing::error!("Type defined in config not one of 'image', 'video', 'shader'");
+                    std::process::exit(1);
+                }
+            },
+            None => {
+                tracing::error!("Type not defined in config");
+                std::process::exit(1);
             }
-            Err(_) => {
-                tracing::warn!("No config found at {:?}, using defaults", config_file);
-                Media::Solid(Color {
-                    r: (SOLID_RED_DEFAULT / 255) as f64,
-                    g: (SOLID_GREEN_DEFAULT / 255) as f64,
-                    b: (SOLID_BLUE_DEFAULT / 255) as f64,
-                    a: (SOLID_ALPHA_DEFAULT / 255) as f64,
-                })
-            }
+        }
+    }
+}
+
+fn required_path(conf: &Config, section: &str, key: &str) -> PathBuf {
+    match conf.get_path(section, key) {
+        Some(Ok(path)) => path,
+        Some(Err(e)) => {
+            tracing::error!("Invalid [{}] {}: {}", section, key, e);
+            std::process::exit(1);
+        }
+        None => {
+            tracing::error!("[{}] {} not defined in config", section, key);
+            std::process::exit(1);
         }
     }
 }
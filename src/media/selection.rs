@@ -0,0 +1,180 @@
+use std::{
+    collections::hash_map::RandomState,
+    fs,
+    hash::{BuildHasher, Hasher},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use image::ImageFormat;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How `[image] path` picks a file when it points at a directory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    Random,
+    Sequential,
+    Daily,
+}
+
+impl FromStr for Selection {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Selection::Random),
+            "sequential" => Ok(Selection::Sequential),
+            "daily" => Ok(Selection::Daily),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Files in `dir` that the enabled `image` decoders can read, sorted by name.
+fn supported_images(dir: &Path) -> Vec<PathBuf> {
+    let mut images: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_file()
+                    && ImageFormat::from_path(path)
+                        .map(|format| format.reading_enabled())
+                        .unwrap_or(false)
+            })
+            .collect(),
+        Err(e) => {
+            tracing::error!("Unable to read image directory {:?}: {}", dir, e);
+            Vec::new()
+        }
+    };
+    images.sort();
+    images
+}
+
+/// Picks one image from `dir`. Sequential mode remembers the last pick in
+/// `state_file` so the next lock moves on to the following file.
+pub fn pick(dir: &Path, selection: Selection, state_file: Option<&Path>) -> Option<PathBuf> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    pick_at(dir, selection, state_file, now)
+}
+
+/// [`pick`] at `now`, the time since the Unix epoch.
+fn pick_at(
+    dir: &Path,
+    selection: Selection,
+    state_file: Option<&Path>,
+    now: Duration,
+) -> Option<PathBuf> {
+    let images = supported_images(dir);
+    if images.is_empty() {
+        return None;
+    }
+
+    let index = match selection {
+        Selection::Random => {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(now.as_nanos());
+            hasher.finish() as usize % images.len()
+        }
+        Selection::Daily => (now.as_secs() / SECONDS_PER_DAY) as usize % images.len(),
+        Selection::Sequential => {
+            let last = state_file
+                .and_then(|f| fs::read_to_string(f).ok())
+                .map(|s| PathBuf::from(s.trim()));
+            match last {
+                Some(last) => images.iter().position(|image| *image > last).unwrap_or(0),
+                None => 0,
+            }
+        }
+    };
+
+    let picked = images[index].clone();
+    if selection == Selection::Sequential {
+        if let Some(f) = state_file {
+            if let Err(e) = fs::write(f, picked.to_string_lossy().as_bytes()) {
+                tracing::warn!("Unable to save wallpaper state to {:?}: {}", f, e);
+            }
+        }
+    }
+    tracing::trace!("Picked {:?} from {:?} ({:?})", picked, dir, selection);
+    Some(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    const DAY: Duration = Duration::from_secs(SECONDS_PER_DAY);
+
+    fn images() -> TempDir {
+        let dir = TempDir::new();
+        for name in ["b.jpg", "a.png", "c.jpeg", "notes.txt", "layers.xcf"] {
+            dir.write(name, "");
+        }
+        // A directory named like an image isn't one.
+        dir.write("d.png/inside.png", "");
+        dir
+    }
+
+    #[test]
+    fn skips_unsupported_files() {
+        let dir = images();
+        assert_eq!(
+            supported_images(dir.path()),
+            ["a.png", "b.jpg", "c.jpeg"].map(|name| dir.path().join(name))
+        );
+        for _ in 0..20 {
+            let picked = pick(dir.path(), Selection::Random, None).unwrap();
+            assert!(supported_images(dir.path()).contains(&picked));
+        }
+    }
+
+    #[test]
+    fn advances_sequentially_and_saves_the_pick() {
+        let dir = images();
+        let state = TempDir::new();
+        let state_file = state.path().join("last");
+        let next = || pick(dir.path(), Selection::Sequential, Some(&state_file)).unwrap();
+
+        assert_eq!(next(), dir.path().join("a.png"));
+        assert_eq!(
+            fs::read_to_string(&state_file).unwrap(),
+            dir.path().join("a.png").to_string_lossy()
+        );
+        assert_eq!(next(), dir.path().join("b.jpg"));
+        assert_eq!(next(), dir.path().join("c.jpeg"));
+        assert_eq!(next(), dir.path().join("a.png"));
+
+        // A file removed since still leads to the one after it by name.
+        fs::write(
+            &state_file,
+            dir.path().join("a.zzz").to_string_lossy().as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(next(), dir.path().join("b.jpg"));
+    }
+
+    #[test]
+    fn keeps_the_daily_pick_all_day() {
+        let dir = images();
+        let day = |now| pick_at(dir.path(), Selection::Daily, None, now).unwrap();
+        let morning = DAY * 20_000;
+        let evening = morning + DAY - Duration::from_secs(1);
+        assert_eq!(day(morning), day(evening));
+        assert_ne!(day(evening), day(morning + DAY));
+    }
+
+    #[test]
+    fn finds_nothing_in_an_empty_directory() {
+        let dir = TempDir::new();
+        assert_eq!(pick(dir.path(), Selection::Random, None), None);
+        assert_eq!(pick(dir.path(), Selection::Sequential, None), None);
+        dir.write("notes.txt", "");
+        assert_eq!(pick(dir.path(), Selection::Daily, None), None);
+    }
+}
@@ -0,0 +1,550 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    os::fd::{AsFd, BorrowedFd},
+    rc::Rc,
+    time::Duration,
+};
+
+use dbus::{
+    arg::{ArgType, PropMap, RefArg},
+    blocking::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged,
+    channel::{BusType, Channel},
+    message::{MatchRule, MessageType, SignalArgs},
+    Message,
+};
+use smithay_client_toolkit::reexports::calloop::{
+    generic::Generic, Interest, LoopHandle, Mode, PostAction,
+};
+
+use crate::app::AppData;
+
+const NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const DBUS_NAME: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// What a player is playing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Track {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub art_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    /// Well-known name, like `org.mpris.MediaPlayer2.spotify`.
+    pub name: String,
+    /// Unique name of the connection owning it, which signals come from.
+    owner: String,
+    pub playing: bool,
+    pub track: Track,
+}
+
+impl Player {
+    /// The name without the MPRIS prefix.
+    pub fn short_name(&self) -> &str {
+        self.name.strip_prefix(NAME_PREFIX).unwrap_or(&self.name)
+    }
+
+    fn apply(&mut self, props: &PropMap) {
+        if let Some(status) = props.get("PlaybackStatus").and_then(|v| v.0.as_str()) {
+            self.playing = status == "Playing";
+        }
+        if let Some(metadata) = props.get("Metadata") {
+            self.track = track(&*metadata.0);
+        }
+    }
+}
+
+/// A player action bound to a media key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    PlayPause,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+}
+
+impl Control {
+    fn method(&self) -> &'static str {
+        match self {
+            Control::PlayPause => "PlayPause",
+            Control::Pause => "Pause",
+            Control::Stop => "Stop",
+            Control::Next => "Next",
+            Control::Previous => "Previous",
+        }
+    }
+}
+
+/// The session bus fd, for the event loop to watch. Holding the channel
+/// keeps the fd open for as long as the event source.
+struct BusFd(Rc<Channel>);
+
+impl AsFd for BusFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.0.watch().fd) }
+    }
+}
+
+/// A call whose reply is still to come, and what it was for.
+enum Pending {
+    ListNames,
+    /// Looking up who owns the player `name`.
+    Owner(String),
+    /// Reading the properties of a player that just showed up.
+    Player {
+        name: String,
+        owner: String,
+    },
+    /// Reading properties a player only said had changed.
+    Refresh(String),
+}
+
+/// Everything on the session bus side: calls are sent without waiting and
+/// their replies handled as they come in with the signals.
+struct Session {
+    bus: Rc<Channel>,
+    players: Rc<RefCell<Vec<Player>>>,
+    pending: HashMap<u32, Pending>,
+}
+
+impl Session {
+    fn call(&mut self, msg: Message, pending: Pending) {
+        match self.bus.send(msg) {
+            Ok(serial) => {
+                self.pending.insert(serial, pending);
+            }
+            Err(()) => tracing::warn!("Unable to send to the session bus"),
+        }
+    }
+
+    fn get_all(&mut self, owner: &str, pending: Pending) {
+        let msg = Message::new_method_call(owner, OBJECT_PATH, PROPERTIES_INTERFACE, "GetAll")
+            .unwrap()
+            .append1(PLAYER_INTERFACE);
+        self.call(msg, pending);
+    }
+
+    /// Reads everything the bus has sent and says whether a player changed.
+    fn dispatch(&mut self) -> Result<bool, ()> {
+        self.bus.read_write(Some(Duration::ZERO))?;
+        let mut changed = false;
+        while let Some(msg) = self.bus.pop_message() {
+            changed |= match msg.msg_type() {
+                MessageType::Signal => self.signal(&msg),
+                MessageType::MethodReturn | MessageType::Error => {
+                    match msg.get_reply_serial().and_then(|s| self.pending.remove(&s)) {
+                        Some(pending) => self.reply(msg, pending),
+                        None => false,
+                    }
+                }
+                _ => false,
+            };
+        }
+        self.bus.flush();
+        Ok(changed)
+    }
+
+    fn signal(&mut self, msg: &Message) -> bool {
+        if msg.interface().as_deref() == Some(DBUS_NAME)
+            && msg.member().as_deref() == Some("NameOwnerChanged")
+        {
+            let Ok((name, _, owner)) = msg.read3::<String, String, String>() else {
+                return false;
+            };
+            if !name.starts_with(NAME_PREFIX) {
+                return false;
+            }
+            self.players.borrow_mut().retain(|p| p.name != name);
+            // Answers about a previous owner are no use any more.
+            self.pending.retain(|_, pending| match pending {
+                Pending::Owner(n) | Pending::Player { name: n, .. } => *n != name,
+                _ => true,
+            });
+            if !owner.is_empty() {
+                self.get_all(&owner.clone(), Pending::Player { name, owner });
+            }
+            return true;
+        }
+        let Some(signal) = PropertiesPropertiesChanged::from_message(msg) else {
+            return false;
+        };
+        if signal.interface_name != PLAYER_INTERFACE || msg.path().as_deref() != Some(OBJECT_PATH) {
+            return false;
+        }
+        let Some(sender) = msg.sender() else {
+            return false;
+        };
+        let owner = sender.to_string();
+        let mut players = self.players.borrow_mut();
+        let Some(player) = players.iter_mut().find(|p| p.owner == owner) else {
+            return false;
+        };
+        player.apply(&signal.changed_properties);
+        drop(players);
+        // Some players only say a property changed, so ask for it.
+        if !signal.invalidated_properties.is_empty() {
+            self.get_all(&owner.clone(), Pending::Refresh(owner));
+        }
+        true
+    }
+
+    fn reply(&mut self, mut msg: Message, pending: Pending) -> bool {
+        if let Err(e) = msg.as_result() {
+            match pending {
+                Pending::ListNames => tracing::warn!("Unable to list media players: {}", e),
+                Pending::Player { name, .. } => tracing::warn!("Ignoring player {}: {}", name, e),
+                // Gone again before it could be asked.
+                Pending::Owner(_) | Pending::Refresh(_) => {}
+            }
+            return false;
+        }
+        match pending {
+            Pending::ListNames => {
+                let names: Vec<String> = msg.read1().unwrap_or_default();
+                for name in names.into_iter().filter(|n| n.starts_with(NAME_PREFIX)) {
+                    let msg =
+                        Message::new_method_call(DBUS_NAME, DBUS_PATH, DBUS_NAME, "GetNameOwner")
+                            .unwrap()
+                            .append1(&name);
+                    self.call(msg, Pending::Owner(name));
+                }
+                false
+            }
+            Pending::Owner(name) => {
+                if let Ok(owner) = msg.read1::<String>() {
+                    self.get_all(&owner.clone(), Pending::Player { name, owner });
+                }
+                false
+            }
+            Pending::Player { name, owner } => {
+                let Ok(props) = msg.read1::<PropMap>() else {
+                    return false;
+                };
+                let mut player = Player {
+                    name,
+                    owner,
+                    playing: false,
+                    track: Track::default(),
+                };
+                player.apply(&props);
+                let mut players = self.players.borrow_mut();
+                // Both the listing and a signal may have found it.
+                players.retain(|p| p.name != player.name);
+                players.push(player);
+                true
+            }
+            Pending::Refresh(owner) => {
+                let Ok(props) = msg.read1::<PropMap>() else {
+                    return false;
+                };
+                let mut players = self.players.borrow_mut();
+                let Some(player) = players.iter_mut().find(|p| p.owner == owner) else {
+                    return false;
+                };
+                player.apply(&props);
+                true
+            }
+        }
+    }
+}
+
+/// Media players on the session bus, kept up to date from their signals on
+/// the event loop.
+pub struct Mpris {
+    bus: Rc<Channel>,
+    players: Rc<RefCell<Vec<Player>>>,
+}
+
+impl Mpris {
+    /// Connects to the session bus and starts looking for players. They
+    /// show up in [`Mpris::players`] as they answer.
+    pub fn connect(events: &LoopHandle<'static, AppData>) -> Result<Self, dbus::Error> {
+        let (mpris, mut session) = Self::open(Channel::get_private(BusType::Session)?);
+        events
+            .insert_source(
+                Generic::new(BusFd(mpris.bus.clone()), Interest::READ, Mode::Level),
+                move |_, _, app_data| {
+                    match session.dispatch() {
+                        Ok(true) => app_data.render_all(),
+                        Ok(false) => {}
+                        Err(()) => {
+                            tracing::warn!("Lost the session bus");
+                            return Ok(PostAction::Remove);
+                        }
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .expect("Failed to insert the session bus source!");
+        Ok(mpris)
+    }
+
+    /// Asks `channel` about players and for their signals, returning the
+    /// session that handles the answers.
+    fn open(mut channel: Channel) -> (Self, Session) {
+        channel.set_watch_enabled(true);
+        let bus = Rc::new(channel);
+        let players: Rc<RefCell<Vec<Player>>> = Rc::default();
+        let mut session = Session {
+            bus: bus.clone(),
+            players: players.clone(),
+            pending: HashMap::new(),
+        };
+
+        let mut properties = PropertiesPropertiesChanged::match_rule(None, None);
+        properties.path = Some(OBJECT_PATH.into());
+        for rule in [
+            MatchRule::new_signal(DBUS_NAME, "NameOwnerChanged"),
+            properties,
+        ] {
+            let add_match = Message::new_method_call(DBUS_NAME, DBUS_PATH, DBUS_NAME, "AddMatch")
+                .unwrap()
+                .append1(rule.match_str());
+            // Nothing to do with the answer, signals just start coming.
+            let _ = bus.send(add_match);
+        }
+        let list = Message::new_method_call(DBUS_NAME, DBUS_PATH, DBUS_NAME, "ListNames").unwrap();
+        session.call(list, Pending::ListNames);
+        bus.flush();
+        (Self { bus, players }, session)
+    }
+
+    pub fn players(&self) -> std::cell::Ref<'_, Vec<Player>> {
+        self.players.borrow()
+    }
+
+    /// Sends `control` to the player named `name`. Nothing waits for an
+    /// answer, players report what happened through their signals.
+    pub fn control(&self, name: &str, control: Control) {
+        let Some(owner) = self
+            .players
+            .borrow()
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.owner.clone())
+        else {
+            return;
+        };
+        let msg = Message::new_method_call(owner, OBJECT_PATH, PLAYER_INTERFACE, control.method())
+            .unwrap();
+        if self.bus.send(msg).is_err() {
+            tracing::warn!("Unable to send {} to {}", control.method(), name);
+        }
+        self.bus.flush();
+    }
+}
+
+/// Reads the fields shown from an `a{sv}` of MPRIS metadata.
+fn track(metadata: &dyn RefArg) -> Track {
+    let mut track = Track::default();
+    let Some(mut entries) = metadata.as_iter() else {
+        return track;
+    };
+    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+        let value = variant(value);
+        match key.as_str() {
+            Some("xesam:title") => track.title = value.as_str().map(str::to_string),
+            Some("xesam:artist") => {
+                let artists: Vec<&str> = value
+                    .as_iter()
+                    .map(|artists| artists.filter_map(|a| a.as_str()).collect())
+                    .unwrap_or_default();
+                if !artists.is_empty() {
+                    track.artist = Some(artists.join(", "));
+                }
+            }
+            Some("mpris:artUrl") => track.art_url = value.as_str().map(str::to_string),
+            _ => {}
+        }
+    }
+    track.title = track.title.filter(|t| !t.trim().is_empty());
+    track
+}
+
+/// What a variant holds, or `value` itself if it isn't one.
+fn variant(value: &dyn RefArg) -> &dyn RefArg {
+    if value.arg_type() == ArgType::Variant {
+        if let Some(inner) = value.as_iter().and_then(|mut i| i.next()) {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use dbus::{arg::Variant, blocking::LocalConnection, channel::MatchingReceiver, strings::Path};
+
+    use super::*;
+    use crate::testing::private_bus;
+
+    const TEST_PLAYER: &str = "org.mpris.MediaPlayer2.test";
+
+    fn metadata(title: &str) -> PropMap {
+        let mut metadata = PropMap::new();
+        metadata.insert(
+            "xesam:title".to_string(),
+            Variant(Box::new(title.to_string())),
+        );
+        metadata.insert(
+            "xesam:artist".to_string(),
+            Variant(Box::new(vec!["One".to_string(), "Two".to_string()])),
+        );
+        metadata.insert(
+            "mpris:artUrl".to_string(),
+            Variant(Box::new("file:///tmp/cover.png".to_string())),
+        );
+        metadata.insert("mpris:length".to_string(), Variant(Box::new(1000i64)));
+        metadata
+    }
+
+    fn properties(status: &str, title: &str) -> PropMap {
+        let mut props = PropMap::new();
+        props.insert(
+            "PlaybackStatus".to_string(),
+            Variant(Box::new(status.to_string())),
+        );
+        props.insert("Metadata".to_string(), Variant(Box::new(metadata(title))));
+        props
+    }
+
+    /// What the test asks the fake player to do.
+    enum Command {
+        Change(&'static str, &'static str),
+        Quit,
+    }
+
+    /// A player paused on "First" that reports the methods called on it.
+    fn fake_player(address: String) -> (mpsc::Sender<Command>, mpsc::Receiver<String>) {
+        let (commands, received) = mpsc::channel();
+        let (events, calls) = mpsc::channel();
+        thread::spawn(move || {
+            let mut channel = Channel::open_private(&address).unwrap();
+            channel.register().unwrap();
+            let conn = LocalConnection::from(channel);
+            conn.request_name(TEST_PLAYER, false, true, true).unwrap();
+            events.send("ready".to_string()).unwrap();
+            conn.start_receive(
+                MatchRule::new_method_call(),
+                Box::new(move |call: Message, conn: &LocalConnection| {
+                    let reply = match call.member().as_deref() {
+                        Some("GetAll") => {
+                            call.method_return().append1(properties("Paused", "First"))
+                        }
+                        Some(method) => {
+                            let _ = events.send(method.to_string());
+                            call.method_return()
+                        }
+                        None => return true,
+                    };
+                    let _ = conn.channel().send(reply);
+                    true
+                }),
+            );
+            loop {
+                conn.process(Duration::from_millis(20)).unwrap();
+                match received.try_recv() {
+                    Ok(Command::Change(status, title)) => {
+                        let signal = PropertiesPropertiesChanged {
+                            interface_name: PLAYER_INTERFACE.to_string(),
+                            changed_properties: properties(status, title),
+                            invalidated_properties: Vec::new(),
+                        };
+                        let _ = conn
+                            .channel()
+                            .send(signal.to_emit_message(&Path::from(OBJECT_PATH)));
+                    }
+                    Ok(Command::Quit) => break,
+                    Err(_) => {}
+                }
+            }
+        });
+        assert_eq!(
+            calls.recv_timeout(Duration::from_secs(5)).as_deref(),
+            Ok("ready")
+        );
+        (commands, calls)
+    }
+
+    /// Dispatches until `done` holds for the players.
+    fn wait_for(mpris: &Mpris, session: &mut Session, done: impl Fn(&[Player]) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            session.dispatch().unwrap();
+            if done(&mpris.players()) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Players never got there: {:?}", mpris.players());
+    }
+
+    #[test]
+    fn reads_tracks() {
+        let track = track(&metadata("Song"));
+        assert_eq!(
+            track,
+            Track {
+                title: Some("Song".to_string()),
+                artist: Some("One, Two".to_string()),
+                art_url: Some("file:///tmp/cover.png".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn skips_blank_titles_and_missing_fields() {
+        assert_eq!(track(&metadata("  ")).title, None);
+        assert_eq!(track(&PropMap::new()), Track::default());
+        // Not a dictionary at all.
+        assert_eq!(track(&"Song".to_string()), Track::default());
+    }
+
+    #[test]
+    fn follows_players_on_the_bus() {
+        let Some((_bus, address)) = private_bus() else {
+            eprintln!("Skipping, dbus-daemon is not available");
+            return;
+        };
+        let (commands, calls) = fake_player(address.clone());
+        let mut channel = Channel::open_private(&address).unwrap();
+        channel.register().unwrap();
+        let (mpris, mut session) = Mpris::open(channel);
+
+        // Found through ListNames and GetAll.
+        wait_for(&mpris, &mut session, |players| !players.is_empty());
+        let player = mpris.players()[0].clone();
+        assert_eq!(player.short_name(), "test");
+        assert!(!player.playing);
+        assert_eq!(player.track.title.as_deref(), Some("First"));
+        assert_eq!(player.track.artist.as_deref(), Some("One, Two"));
+
+        commands.send(Command::Change("Playing", "Second")).unwrap();
+        wait_for(&mpris, &mut session, |players| {
+            players[0].playing && players[0].track.title.as_deref() == Some("Second")
+        });
+
+        mpris.control(TEST_PLAYER, Control::PlayPause);
+        assert_eq!(
+            calls.recv_timeout(Duration::from_secs(5)).as_deref(),
+            Ok("PlayPause")
+        );
+
+        // Its name goes away with the connection.
+        commands.send(Command::Quit).unwrap();
+        wait_for(&mpris, &mut session, |players| players.is_empty());
+    }
+}
@@ -1,9 +1,50 @@
-use pam::Client;
-
-pub fn auth(service: String, username: String, password: String) -> Result<(), pam::PamError> {
-    let mut client = Client::with_password(service.as_str()).unwrap();
-    client
-        .conversation_mut()
-        .set_credentials(username, password);
-    client.authenticate()
+use std::ffi::{CStr, CString};
+
+use pam::{Client, Conversation};
+
+/// Answers PAM's prompts with the typed credentials and keeps any info or
+/// error text it sends so it can be shown on the lock screen.
+struct LockConversation {
+    username: String,
+    password: String,
+    messages: Vec<String>,
+}
+
+impl Conversation for LockConversation {
+    fn prompt_echo(&mut self, _msg: &CStr) -> Result<CString, ()> {
+        CString::new(self.username.clone()).map_err(|_| ())
+    }
+
+    fn prompt_blind(&mut self, _msg: &CStr) -> Result<CString, ()> {
+        CString::new(self.password.clone()).map_err(|_| ())
+    }
+
+    fn info(&mut self, msg: &CStr) {
+        tracing::info!("PAM: {}", msg.to_string_lossy());
+        self.messages.push(msg.to_string_lossy().into_owned());
+    }
+
+    fn error(&mut self, msg: &CStr) {
+        tracing::warn!("PAM: {}", msg.to_string_lossy());
+        self.messages.push(msg.to_string_lossy().into_owned());
+    }
+}
+
+/// Authenticates `username` against `service`. Info and error text from the
+/// PAM conversation is appended to `messages`.
+pub fn auth(
+    service: String,
+    username: String,
+    password: String,
+    messages: &mut Vec<String>,
+) -> Result<(), pam::PamError> {
+    let conversation = LockConversation {
+        username,
+        password,
+        messages: Vec::new(),
+    };
+    let mut client = Client::with_conversation(service.as_str(), conversation).unwrap();
+    let result = client.authenticate();
+    messages.append(&mut client.conversation_mut().messages);
+    result
 }
@@ -0,0 +1,204 @@
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+
+use crate::app::AppData;
+use crate::config::Config;
+
+const SECTION: &str = "transition";
+const DURATION_DEFAULT: f32 = 0.3;
+/// Longest an unlock may wait on its animation, whatever `duration` says.
+const MAX_DELAY_DEFAULT: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    None,
+    /// From black when locking and back to black when unlocking. The lock
+    /// may not show the desktop underneath, so black is as transparent as
+    /// it gets.
+    Fade,
+}
+
+impl FromStr for Kind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Kind::None),
+            "fade" => Ok(Kind::Fade),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    In,
+    Out,
+}
+
+/// The animation played when the lock screen appears and goes away. Frames
+/// are driven by the compositor's frame callbacks.
+pub struct Transition {
+    kind: Kind,
+    duration: Duration,
+    /// Length of the exit animation: `duration`, cut short so the unlock
+    /// is never held up for long.
+    cap: Duration,
+    running: Option<(Direction, Instant)>,
+}
+
+impl Transition {
+    /// Reads `[transition]`: `type` is `fade` or `none`, and `duration` and
+    /// `max_delay` are in seconds.
+    pub fn from_config(conf: &Config) -> Self {
+        let kind = conf
+            .get(SECTION, "type")
+            .map(|s| {
+                s.parse::<Kind>().unwrap_or_else(|_| {
+                    tracing::warn!("Unknown transition '{}', expected 'fade' or 'none'", s);
+                    Kind::None
+                })
+            })
+            .unwrap_or(Kind::None);
+        let secs = |key: &str, default: f32| {
+            let secs = conf
+                .get(SECTION, key)
+                .and_then(|v| v.parse::<f32>().ok())
+                .filter(|s| s.is_finite() && *s >= 0.0)
+                .unwrap_or(default);
+            Duration::from_secs_f32(secs)
+        };
+        let duration = secs("duration", DURATION_DEFAULT);
+        Self {
+            kind: if duration.is_zero() { Kind::None } else { kind },
+            duration,
+            cap: secs("max_delay", MAX_DELAY_DEFAULT).min(duration),
+            running: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn is_leaving(&self) -> bool {
+        matches!(self.running, Some((Direction::Out, _)))
+    }
+
+    fn start(&mut self, direction: Direction) {
+        if self.kind != Kind::None {
+            self.running = Some((direction, Instant::now()));
+        }
+    }
+
+    /// Darkness over the frame at this point of the animation.
+    pub fn dim(&self) -> f32 {
+        let Some((direction, start)) = self.running else {
+            return 0.0;
+        };
+        let duration = match direction {
+            Direction::In => self.duration,
+            Direction::Out => self.cap,
+        };
+        let progress = (start.elapsed().as_secs_f32() / duration.as_secs_f32()).min(1.0);
+        match direction {
+            Direction::In => 1.0 - progress,
+            Direction::Out => progress,
+        }
+    }
+
+    fn finished(&self) -> bool {
+        let Some((direction, start)) = self.running else {
+            return true;
+        };
+        match direction {
+            Direction::In => start.elapsed() >= self.duration,
+            Direction::Out => start.elapsed() >= self.cap,
+        }
+    }
+}
+
+impl AppData {
+    /// Starts fading the lock screen in.
+    pub fn transition_in(&mut self) {
+        self.lock_data.transition.start(Direction::In);
+        if !self.lock_data.transition.is_running() {
+            return;
+        }
+        // Without frame callbacks the screen would otherwise stay dark.
+        let duration = self.lock_data.transition.duration;
+        self.wayland
+            .loop_handle
+            .insert_source(Timer::from_duration(duration), |_, _, app_data| {
+                app_data.finish_transition_in();
+                TimeoutAction::Drop
+            })
+            .expect("Failed to insert the transition timer!");
+    }
+
+    /// Stops fading in, unless the lock is already on its way out.
+    fn finish_transition_in(&mut self) {
+        let transition = &mut self.lock_data.transition;
+        if transition.is_running() && !transition.is_leaving() {
+            transition.running = None;
+            self.render_all();
+        }
+    }
+
+    /// Ends the lock once the user has authenticated, after the exit
+    /// animation if there is one.
+    pub fn unlock(&mut self) {
+        if self.lock_data.transition.is_leaving() || self.exit {
+            return;
+        }
+        // The last frames should be seen, and the session shouldn't come
+        // back on dark outputs.
+        self.wake();
+        self.lock_data.transition.start(Direction::Out);
+        if !self.lock_data.transition.is_running() {
+            self.finish_unlock();
+            return;
+        }
+        // Frame callbacks stop coming for outputs that are off or hidden,
+        // so a timer makes sure the unlock happens regardless.
+        let cap = self.lock_data.transition.cap;
+        self.wayland
+            .loop_handle
+            .insert_source(Timer::from_duration(cap), |_, _, app_data| {
+                app_data.finish_unlock();
+                TimeoutAction::Drop
+            })
+            .expect("Failed to insert the unlock timer!");
+        self.render_all();
+    }
+
+    fn finish_unlock(&mut self) {
+        if self.exit {
+            return;
+        }
+        tracing::trace!("Unlocked!");
+        self.lock_data.unlock();
+        self.wayland.conn.roundtrip().unwrap();
+        self.exit = true;
+    }
+
+    /// Draws the next frame of a running animation on the surface at
+    /// `index`, and finishes it once it has played out.
+    pub fn transition_frame(&mut self, index: usize) {
+        self.lock_data.session_lock_surfaces[index].frame_pending = false;
+        if !self.lock_data.transition.is_running() {
+            return;
+        }
+        if !self.lock_data.transition.finished() {
+            self.render_surface(index);
+        } else if self.lock_data.transition.is_leaving() {
+            self.finish_unlock();
+        } else {
+            self.finish_transition_in();
+        }
+    }
+}
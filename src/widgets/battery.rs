@@ -0,0 +1,472 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use smithay_client_toolkit::reexports::calloop::LoopHandle;
+
+use crate::app::AppData;
+use crate::config::Config;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::text::{TextSection, TextStyle};
+
+use super::{align_x, color, text_style, value, Anchor, Frame, Widget};
+
+const ROOT_DEFAULT: &str = "/sys/class/power_supply";
+const FORMAT_DEFAULT: &str = "{percent}% {state} {time}";
+const INTERVAL_DEFAULT: u64 = 30;
+const LOW_THRESHOLD_DEFAULT: f32 = 15.0;
+const SIZE_DEFAULT: f32 = 14.0;
+const LOW_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.9,
+    g: 0.2,
+    b: 0.2,
+    a: 1.0,
+};
+pub const ANCHOR_DEFAULT: Anchor = Anchor::TopRight;
+pub const OFFSET_DEFAULT: (f32, f32) = (-20.0, 20.0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChargeState {
+    Charging,
+    Discharging,
+    Full,
+    /// Plugged in but held below full, e.g. by a charge threshold.
+    NotCharging,
+    Unknown,
+}
+
+impl ChargeState {
+    fn from_sysfs(status: &str) -> Self {
+        match status {
+            "Charging" => ChargeState::Charging,
+            "Discharging" => ChargeState::Discharging,
+            "Full" => ChargeState::Full,
+            "Not charging" => ChargeState::NotCharging,
+            _ => ChargeState::Unknown,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            ChargeState::Charging => "charging",
+            ChargeState::Discharging => "on battery",
+            ChargeState::Full => "full",
+            ChargeState::NotCharging => "plugged in",
+            ChargeState::Unknown => "",
+        }
+    }
+}
+
+/// Charge of all system batteries taken together.
+#[derive(Debug, Clone, Copy)]
+struct PowerStatus {
+    percent: f32,
+    state: ChargeState,
+    time_remaining: Option<Duration>,
+}
+
+fn read_value(dir: &Path, name: &str) -> Option<f64> {
+    fs::read_to_string(dir.join(name)).ok()?.trim().parse().ok()
+}
+
+fn read_text(dir: &Path, name: &str) -> Option<String> {
+    Some(fs::read_to_string(dir.join(name)).ok()?.trim().to_string())
+}
+
+/// Energy now, energy when full and the rate of charge or discharge of one
+/// battery. Batteries reporting charge in µAh are converted with their
+/// voltage so several can be added up.
+fn battery_energy(dir: &Path) -> Option<(f64, f64, f64)> {
+    if let (Some(now), Some(full)) = (
+        read_value(dir, "energy_now"),
+        read_value(dir, "energy_full"),
+    ) {
+        let rate = read_value(dir, "power_now").unwrap_or(0.0);
+        return Some((now, full, rate.abs()));
+    }
+    let now = read_value(dir, "charge_now")?;
+    let full = read_value(dir, "charge_full")?;
+    let rate = read_value(dir, "current_now").unwrap_or(0.0).abs();
+    let volts = read_value(dir, "voltage_now").map_or(1.0, |uv| uv / 1e6);
+    Some((now * volts, full * volts, rate * volts))
+}
+
+/// Reads the batteries under `root`, laid out like
+/// `/sys/class/power_supply`. Device batteries such as those of wireless
+/// mice are left out. Returns `None` when there is no system battery.
+fn read_status(root: &Path) -> Option<PowerStatus> {
+    let mut batteries: Vec<PathBuf> = fs::read_dir(root)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|dir| read_text(dir, "type").as_deref() == Some("Battery"))
+        .filter(|dir| read_text(dir, "scope").as_deref() != Some("Device"))
+        .collect();
+    if batteries.is_empty() {
+        return None;
+    }
+    batteries.sort();
+
+    let mut state = ChargeState::Unknown;
+    let (mut now, mut full, mut rate) = (0.0, 0.0, 0.0);
+    let mut capacities = Vec::new();
+    for dir in &batteries {
+        let battery_state = ChargeState::from_sysfs(&read_text(dir, "status").unwrap_or_default());
+        // One battery charging or draining decides the overall state.
+        if state == ChargeState::Unknown
+            || matches!(
+                battery_state,
+                ChargeState::Charging | ChargeState::Discharging
+            )
+        {
+            state = battery_state;
+        }
+        if let Some((n, f, r)) = battery_energy(dir) {
+            now += n;
+            full += f;
+            rate += r;
+        }
+        if let Some(capacity) = read_value(dir, "capacity") {
+            capacities.push(capacity);
+        }
+    }
+
+    let percent = if full > 0.0 {
+        now / full * 100.0
+    } else if !capacities.is_empty() {
+        capacities.iter().sum::<f64>() / capacities.len() as f64
+    } else {
+        return None;
+    };
+    let hours = match state {
+        ChargeState::Discharging if rate > 0.0 => Some(now / rate),
+        ChargeState::Charging if rate > 0.0 => Some((full - now).max(0.0) / rate),
+        _ => None,
+    };
+    Some(PowerStatus {
+        percent: percent.clamp(0.0, 100.0) as f32,
+        state,
+        time_remaining: hours.map(|h| Duration::from_secs_f64(h * 3600.0)),
+    })
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Charge, charging state and time remaining of the laptop's batteries,
+/// re-read every `interval` seconds.
+pub struct Battery {
+    root: PathBuf,
+    format: String,
+    interval: Duration,
+    low_threshold: f32,
+    style: TextStyle,
+    low_style: TextStyle,
+    status: Option<PowerStatus>,
+    next_read: Instant,
+    section: Option<TextSection>,
+}
+
+impl Battery {
+    /// Builds a battery widget from `section`. `root` points somewhere other
+    /// than `/sys/class/power_supply`, e.g. at a fake tree for testing.
+    pub fn from_config(conf: &Config, section: &str, anchor: Anchor) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        let root = match conf.get_path(section, "root") {
+            Some(Ok(root)) => root,
+            Some(Err(e)) => {
+                tracing::warn!("Skipping [{}]: {}", section, e);
+                return None;
+            }
+            None => PathBuf::from(ROOT_DEFAULT),
+        };
+        let style = text_style(conf, section, "", SIZE_DEFAULT, anchor.align());
+        let mut low_style = text_style(conf, section, "low_", style.size, anchor.align());
+        low_style.color = color(conf, section, "low_color", LOW_COLOR_DEFAULT);
+        if conf.get(section, "low_font").is_none() {
+            low_style.font = style.font.clone();
+        }
+        let status = read_status(&root);
+        if status.is_none() {
+            tracing::trace!("No battery found under {:?}", root);
+        }
+        let interval =
+            Duration::from_secs(value(conf, section, "interval", INTERVAL_DEFAULT).max(1));
+        Some(Self {
+            root,
+            format: conf
+                .get(section, "format")
+                .unwrap_or(FORMAT_DEFAULT)
+                .to_string(),
+            interval,
+            low_threshold: value(conf, section, "low_threshold", LOW_THRESHOLD_DEFAULT),
+            style,
+            low_style,
+            status,
+            next_read: Instant::now() + interval,
+            section: None,
+        })
+    }
+
+    fn text(&self, status: &PowerStatus) -> String {
+        let text = self
+            .format
+            .replace("{percent}", &format!("{:.0}", status.percent))
+            .replace("{state}", status.state.label())
+            .replace(
+                "{time}",
+                &status
+                    .time_remaining
+                    .map(format_duration)
+                    .unwrap_or_default(),
+            );
+        // Placeholders that came out empty would leave gaps behind.
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn is_low(&self, status: &PowerStatus) -> bool {
+        status.state == ChargeState::Discharging && status.percent <= self.low_threshold
+    }
+}
+
+impl Widget for Battery {
+    fn update(&mut self, _events: &LoopHandle<'static, AppData>) -> Option<Duration> {
+        // Other widgets may ask for updates more often, so sysfs is only
+        // read once `interval` has passed.
+        let now = Instant::now();
+        if now >= self.next_read {
+            self.status = read_status(&self.root);
+            self.next_read = now + self.interval;
+        }
+        Some(self.next_read - now)
+    }
+
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        let Some(status) = self.status else {
+            self.section = None;
+            return (0.0, 0.0);
+        };
+        let style = if self.is_low(&status) {
+            &self.low_style
+        } else {
+            &self.style
+        };
+        let section = TextSection {
+            text: self.text(&status),
+            style: style.clone(),
+            position: (0.0, 0.0),
+        };
+        let size = frame.text.measure(&section, frame.scale_factor as f32);
+        self.section = Some(section);
+        size
+    }
+
+    fn draw(
+        &mut self,
+        _frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        if let Some(mut section) = self.section.take() {
+            section.position = (align_x(section.style.align, origin.0, size.0), origin.1);
+            overlay.texts.push(section);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::text::Align;
+    use crate::testing::TempDir;
+
+    /// Adds a power supply called `name` with sysfs `attributes`.
+    fn supply(root: &TempDir, name: &str, attributes: &[(&str, &str)]) {
+        for (attribute, value) in attributes {
+            root.write(&format!("{}/{}", name, attribute), &format!("{}\n", value));
+        }
+    }
+
+    fn battery(low_threshold: f32) -> Battery {
+        let style = TextStyle {
+            font: "sans-serif".to_string(),
+            size: SIZE_DEFAULT,
+            color: wgpu::Color::WHITE,
+            align: Align::Left,
+        };
+        Battery {
+            root: PathBuf::from(ROOT_DEFAULT),
+            format: FORMAT_DEFAULT.to_string(),
+            interval: Duration::from_secs(INTERVAL_DEFAULT),
+            low_threshold,
+            low_style: style.clone(),
+            style,
+            status: None,
+            next_read: Instant::now(),
+            section: None,
+        }
+    }
+
+    fn status(percent: f32, state: ChargeState) -> PowerStatus {
+        PowerStatus {
+            percent,
+            state,
+            time_remaining: None,
+        }
+    }
+
+    #[test]
+    fn reads_energy() {
+        let root = TempDir::new();
+        supply(
+            &root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "30000000"),
+                ("energy_full", "60000000"),
+                ("power_now", "10000000"),
+            ],
+        );
+        let status = read_status(root.path()).unwrap();
+        assert_eq!(status.percent, 50.0);
+        assert_eq!(status.state, ChargeState::Discharging);
+        assert_eq!(status.time_remaining, Some(Duration::from_secs(3 * 3600)));
+    }
+
+    #[test]
+    fn converts_charge_with_the_voltage() {
+        let root = TempDir::new();
+        supply(
+            &root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Charging"),
+                ("charge_now", "1000000"),
+                ("charge_full", "4000000"),
+                ("current_now", "-1500000"),
+                ("voltage_now", "12000000"),
+            ],
+        );
+        let status = read_status(root.path()).unwrap();
+        assert_eq!(status.percent, 25.0);
+        assert_eq!(status.state, ChargeState::Charging);
+        assert_eq!(status.time_remaining, Some(Duration::from_secs(2 * 3600)));
+    }
+
+    #[test]
+    fn adds_up_system_batteries() {
+        let root = TempDir::new();
+        supply(
+            &root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Full"),
+                ("charge_now", "2000000"),
+                ("charge_full", "2000000"),
+                ("voltage_now", "10000000"),
+            ],
+        );
+        supply(
+            &root,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("energy_now", "10000000"),
+                ("energy_full", "40000000"),
+                ("power_now", "5000000"),
+            ],
+        );
+        supply(&root, "AC", &[("type", "Mains"), ("online", "0")]);
+        supply(
+            &root,
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+                ("capacity", "5"),
+            ],
+        );
+        let status = read_status(root.path()).unwrap();
+        assert_eq!(status.percent, 50.0);
+        // The draining battery wins over the full one.
+        assert_eq!(status.state, ChargeState::Discharging);
+        assert_eq!(status.time_remaining, Some(Duration::from_secs(6 * 3600)));
+    }
+
+    #[test]
+    fn has_no_time_when_full() {
+        let root = TempDir::new();
+        supply(
+            &root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Full"),
+                ("energy_now", "50000000"),
+                ("energy_full", "50000000"),
+                ("power_now", "0"),
+            ],
+        );
+        let status = read_status(root.path()).unwrap();
+        assert_eq!(status.percent, 100.0);
+        assert_eq!(status.state, ChargeState::Full);
+        assert_eq!(status.time_remaining, None);
+    }
+
+    #[test]
+    fn falls_back_to_capacity() {
+        let root = TempDir::new();
+        supply(
+            &root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Unknown"),
+                ("capacity", "42"),
+            ],
+        );
+        let status = read_status(root.path()).unwrap();
+        assert_eq!(status.percent, 42.0);
+        assert_eq!(status.state, ChargeState::Unknown);
+    }
+
+    #[test]
+    fn finds_nothing_without_a_battery() {
+        let root = TempDir::new();
+        assert!(read_status(&root.path().join("missing")).is_none());
+        supply(&root, "AC", &[("type", "Mains"), ("online", "1")]);
+        assert!(read_status(root.path()).is_none());
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::ZERO), "0:00");
+        assert_eq!(format_duration(Duration::from_secs(59)), "0:00");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 5 * 60)),
+            "3:05"
+        );
+        assert_eq!(format_duration(Duration::from_secs(125 * 60)), "2:05");
+    }
+
+    #[test]
+    fn is_low_only_while_discharging() {
+        let battery = battery(15.0);
+        assert!(battery.is_low(&status(15.0, ChargeState::Discharging)));
+        assert!(!battery.is_low(&status(15.5, ChargeState::Discharging)));
+        assert!(!battery.is_low(&status(5.0, ChargeState::Charging)));
+        assert!(!battery.is_low(&status(5.0, ChargeState::NotCharging)));
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use chrono::{
+    format::{Item, Numeric, StrftimeItems},
+    Local, Timelike,
+};
+
+use smithay_client_toolkit::reexports::calloop::LoopHandle;
+
+use crate::app::AppData;
+use crate::config::Config;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::text::{TextSection, TextStyle};
+
+use super::{align_x, text_style, value, Anchor, Frame, Widget};
+
+const FORMAT_24H_DEFAULT: &str = "%H:%M";
+const FORMAT_12H_DEFAULT: &str = "%I:%M %p";
+const DATE_FORMAT_DEFAULT: &str = "%A, %d %B";
+const TIME_SIZE_DEFAULT: f32 = 48.0;
+const DATE_SIZE_DEFAULT: f32 = 16.0;
+pub const ANCHOR_DEFAULT: Anchor = Anchor::Center;
+pub const OFFSET_DEFAULT: (f32, f32) = (0.0, 0.0);
+/// Fire a little after the boundary so the new value is always shown.
+const TICK_SLACK: Duration = Duration::from_millis(5);
+
+fn is_valid(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+fn shows_seconds(format: &str) -> bool {
+    StrftimeItems::new(format).any(|item| {
+        matches!(
+            item,
+            Item::Numeric(
+                Numeric::Second | Numeric::Timestamp | Numeric::Nanosecond,
+                _
+            )
+        )
+    })
+}
+
+/// Time and an optional date line, redrawn on the minute or, when a format
+/// shows seconds, on the second.
+pub struct Clock {
+    format: String,
+    date_format: Option<String>,
+    time_style: TextStyle,
+    date_style: TextStyle,
+    every_second: bool,
+    sections: Vec<(TextSection, (f32, f32))>,
+}
+
+impl Clock {
+    /// Builds a clock from `section`; returns `None` if `enabled=false`.
+    /// Text lines up with the edge given by `anchor`.
+    pub fn from_config(conf: &Config, section: &str, anchor: Anchor) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+
+        let default_format = if value(conf, section, "hour12", false) {
+            FORMAT_12H_DEFAULT
+        } else {
+            FORMAT_24H_DEFAULT
+        };
+        let checked = |key: &str, default: &str| match conf.get(section, key) {
+            Some(format) if !is_valid(format) => {
+                tracing::warn!(
+                    "Invalid [{}] {} '{}', using '{}'",
+                    section,
+                    key,
+                    format,
+                    default
+                );
+                default.to_string()
+            }
+            Some(format) => format.to_string(),
+            None => default.to_string(),
+        };
+        let format = checked("format", default_format);
+        let date_format =
+            Some(checked("date_format", DATE_FORMAT_DEFAULT)).filter(|f| !f.is_empty());
+
+        let every_second =
+            shows_seconds(&format) || date_format.as_deref().is_some_and(shows_seconds);
+        Some(Self {
+            time_style: text_style(conf, section, "", TIME_SIZE_DEFAULT, anchor.align()),
+            date_style: text_style(conf, section, "date_", DATE_SIZE_DEFAULT, anchor.align()),
+            format,
+            date_format,
+            every_second,
+            sections: Vec::new(),
+        })
+    }
+
+    /// Time left until the displayed text next changes.
+    fn until_next_tick(&self) -> Duration {
+        let now = Local::now();
+        let elapsed = Duration::from_nanos(now.nanosecond().min(999_999_999) as u64);
+        let period = if self.every_second {
+            Duration::from_secs(1)
+        } else {
+            Duration::from_secs(60 - now.second() as u64)
+        };
+        period.saturating_sub(elapsed) + TICK_SLACK
+    }
+}
+
+impl Widget for Clock {
+    fn update(&mut self, _events: &LoopHandle<'static, AppData>) -> Option<Duration> {
+        Some(self.until_next_tick())
+    }
+
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        let now = Local::now();
+        let mut lines = vec![(now.format(&self.format).to_string(), &self.time_style)];
+        if let Some(date_format) = &self.date_format {
+            lines.push((now.format(date_format).to_string(), &self.date_style));
+        }
+
+        let scale = frame.scale_factor as f32;
+        self.sections = lines
+            .into_iter()
+            .map(|(text, style)| {
+                let section = TextSection {
+                    text,
+                    style: style.clone(),
+                    position: (0.0, 0.0),
+                };
+                let size = frame.text.measure(&section, scale);
+                (section, size)
+            })
+            .collect();
+        self.sections.iter().fold((0.0f32, 0.0f32), |acc, (_, s)| {
+            (acc.0.max(s.0), acc.1 + s.1)
+        })
+    }
+
+    fn draw(
+        &mut self,
+        _frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        let mut y = origin.1;
+        for (mut section, line_size) in self.sections.drain(..) {
+            section.position = (align_x(section.style.align, origin.0, size.0), y);
+            y += line_size.1;
+            overlay.texts.push(section);
+        }
+    }
+}
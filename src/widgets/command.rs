@@ -0,0 +1,605 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    os::{fd::OwnedFd, unix::process::CommandExt},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use smithay_client_toolkit::reexports::calloop::{
+    channel::{self, Channel},
+    generic::Generic,
+    timer::{TimeoutAction, Timer},
+    Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+};
+
+use crate::app::AppData;
+use crate::config::Config;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::text::{TextSection, TextStyle};
+
+use super::{align_x, color, text_style, value, Anchor, Frame, Widget};
+
+const INTERVAL_DEFAULT: u64 = 60;
+const TIMEOUT_DEFAULT: u64 = 5;
+const SIZE_DEFAULT: f32 = 14.0;
+/// Output beyond this is dropped; the widget only shows a few lines.
+const MAX_OUTPUT: usize = 64 * 1024;
+pub const ANCHOR_DEFAULT: Anchor = Anchor::TopLeft;
+pub const OFFSET_DEFAULT: (f32, f32) = (20.0, 20.0);
+
+/// How the output is read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    /// The output is the text.
+    Text,
+    /// A JSON object with `text` and optionally `class`, as waybar uses.
+    Json,
+    /// JSON if the output looks like an object, text otherwise.
+    Auto,
+}
+
+enum Source {
+    Command(String),
+    File(PathBuf),
+}
+
+/// What the widget shows, shared with the event sources of a running
+/// command.
+#[derive(Default)]
+struct Output {
+    text: String,
+    class: Option<String>,
+}
+
+/// A command started on the event loop, with the pieces that must be torn
+/// down when it finishes or runs out of time.
+struct Run {
+    child: Child,
+    output: Vec<u8>,
+    timer: Option<RegistrationToken>,
+    reader: Option<RegistrationToken>,
+}
+
+impl Run {
+    /// Kills the shell along with anything it started, which would
+    /// otherwise keep running after it.
+    fn kill(&mut self) {
+        // The child leads its own process group, so this reaches the
+        // whole pipeline.
+        unsafe { libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL) };
+        let _ = self.child.wait();
+    }
+}
+
+/// Text from a shell command or a file, refreshed every `interval`
+/// seconds. Commands run without blocking the lock screen and are killed
+/// after `timeout` seconds.
+pub struct CommandWidget {
+    name: String,
+    source: Source,
+    format: Format,
+    interval: Option<Duration>,
+    timeout: Duration,
+    style: TextStyle,
+    class_styles: HashMap<String, TextStyle>,
+    output: Rc<RefCell<Output>>,
+    run: Rc<RefCell<Option<Run>>>,
+    /// Whether a file read is still going.
+    reading: Rc<Cell<bool>>,
+    next_run: Option<Instant>,
+    section: Option<(TextSection, (f32, f32))>,
+}
+
+impl CommandWidget {
+    /// Builds the widget from `section`, which needs either `command` or
+    /// `file`. Keys like `critical_color` style output whose class is
+    /// `critical`.
+    pub fn from_config(conf: &Config, section: &str, anchor: Anchor) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        let source = match (conf.get(section, "command"), conf.get_path(section, "file")) {
+            (Some(command), _) => Source::Command(command.to_string()),
+            (None, Some(Ok(path))) => Source::File(path),
+            (None, Some(Err(e))) => {
+                tracing::warn!("Skipping [{}]: {}", section, e);
+                return None;
+            }
+            (None, None) => {
+                tracing::warn!("Skipping [{}]: needs a command or a file", section);
+                return None;
+            }
+        };
+        let format = match conf.get(section, "format") {
+            None | Some("auto") => Format::Auto,
+            Some("text") => Format::Text,
+            Some("json") => Format::Json,
+            Some(other) => {
+                tracing::warn!(
+                    "Invalid [{}] format '{}', expected auto, text or json",
+                    section,
+                    other
+                );
+                Format::Auto
+            }
+        };
+        let interval = value(conf, section, "interval", INTERVAL_DEFAULT);
+        let style = text_style(conf, section, "", SIZE_DEFAULT, anchor.align());
+        Some(Self {
+            name: section.to_string(),
+            source,
+            format,
+            interval: (interval > 0).then(|| Duration::from_secs(interval)),
+            timeout: Duration::from_secs(value(conf, section, "timeout", TIMEOUT_DEFAULT).max(1)),
+            class_styles: class_styles(conf, section, &style),
+            style,
+            output: Rc::default(),
+            run: Rc::default(),
+            reading: Rc::default(),
+            next_run: Some(Instant::now()),
+            section: None,
+        })
+    }
+
+    /// Spawns the command and hands its output to the event loop.
+    fn spawn(&mut self, command: &str, events: &LoopHandle<'static, AppData>) {
+        let mut child = match Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                tracing::warn!("[{}] unable to run '{}': {}", self.name, command, e);
+                return;
+            }
+        };
+        let stdout = File::from(OwnedFd::from(child.stdout.take().unwrap()));
+        *self.run.borrow_mut() = Some(Run {
+            child,
+            output: Vec::new(),
+            timer: None,
+            reader: None,
+        });
+
+        let (name, format) = (self.name.clone(), self.format);
+        let (run, output) = (self.run.clone(), self.output.clone());
+        let reader = events.insert_source(
+            Generic::new(stdout, Interest::READ, Mode::Level),
+            move |_, stdout, app_data| {
+                let mut buffer = [0; 4096];
+                // The fd is readable, so this returns data or end of file
+                // without blocking.
+                let read = (&**stdout).read(&mut buffer).unwrap_or(0);
+                let mut guard = run.borrow_mut();
+                let Some(current) = guard.as_mut() else {
+                    return Ok(PostAction::Remove);
+                };
+                if read > 0 {
+                    if current.output.len() < MAX_OUTPUT {
+                        current.output.extend_from_slice(&buffer[..read]);
+                    }
+                    return Ok(PostAction::Continue);
+                }
+
+                let mut finished = guard.take().unwrap();
+                if let Some(timer) = finished.timer.take() {
+                    app_data.wayland.loop_handle.remove(timer);
+                }
+                match finished.child.try_wait() {
+                    Ok(Some(status)) if !status.success() => {
+                        tracing::warn!("[{}] command exited with {}", name, status);
+                    }
+                    Ok(Some(_)) => {}
+                    // Closed its output but kept running, so it won't be
+                    // needed any more.
+                    _ => finished.kill(),
+                }
+                *output.borrow_mut() = parse(&String::from_utf8_lossy(&finished.output), format);
+                drop(guard);
+                app_data.render_all();
+                Ok(PostAction::Remove)
+            },
+        );
+        let reader = match reader {
+            Ok(token) => token,
+            Err(e) => {
+                tracing::warn!("[{}] unable to watch the command: {}", self.name, e);
+                if let Some(mut run) = self.run.borrow_mut().take() {
+                    run.kill();
+                }
+                return;
+            }
+        };
+
+        let (name, run) = (self.name.clone(), self.run.clone());
+        let timer = events
+            .insert_source(Timer::from_duration(self.timeout), move |_, _, app_data| {
+                if let Some(mut timed_out) = run.borrow_mut().take() {
+                    tracing::warn!("[{}] command timed out, killing it", name);
+                    timed_out.kill();
+                    if let Some(reader) = timed_out.reader.take() {
+                        app_data.wayland.loop_handle.remove(reader);
+                    }
+                }
+                TimeoutAction::Drop
+            })
+            .expect("Failed to insert the command timer!");
+        if let Some(run) = self.run.borrow_mut().as_mut() {
+            run.reader = Some(reader);
+            run.timer = Some(timer);
+        }
+    }
+
+    /// Reads the file on its own thread, as files on network mounts or
+    /// from slow drivers can take their time, and hands the contents to the
+    /// event loop.
+    fn read(&mut self, path: PathBuf, events: &LoopHandle<'static, AppData>) {
+        let (sender, receiver): (_, Channel<std::io::Result<Vec<u8>>>) = channel::channel();
+        let (name, format) = (self.name.clone(), self.format);
+        let (reading, output) = (self.reading.clone(), self.output.clone());
+        let file = path.clone();
+        // The source goes away by itself once the thread drops the sender.
+        let inserted = events.insert_source(receiver, move |event, _, app_data| {
+            let channel::Event::Msg(contents) = event else {
+                return;
+            };
+            reading.set(false);
+            match contents {
+                Ok(contents) => {
+                    *output.borrow_mut() = parse(&String::from_utf8_lossy(&contents), format);
+                    app_data.render_all();
+                }
+                Err(e) => tracing::warn!("[{}] unable to read {:?}: {}", name, file, e),
+            }
+        });
+        if let Err(e) = inserted {
+            tracing::warn!("[{}] unable to watch {:?}: {}", self.name, path, e);
+            return;
+        }
+        self.reading.set(true);
+        thread::spawn(move || {
+            let mut contents = Vec::new();
+            let read = File::open(&path)
+                .and_then(|file| file.take(MAX_OUTPUT as u64).read_to_end(&mut contents));
+            let _ = sender.send(read.map(|_| contents));
+        });
+    }
+}
+
+impl Widget for CommandWidget {
+    fn update(&mut self, events: &LoopHandle<'static, AppData>) -> Option<Duration> {
+        let now = Instant::now();
+        let next_run = self.next_run?;
+        if now < next_run {
+            return Some(next_run - now);
+        }
+        self.next_run = self.interval.map(|interval| now + interval);
+
+        match &self.source {
+            // A run or read that is still going is left alone rather than
+            // piling up another one next to it.
+            Source::File(_) if self.reading.get() => {}
+            Source::Command(_) if self.run.borrow().is_some() => {}
+            Source::File(path) => {
+                let path = path.clone();
+                self.read(path, events);
+            }
+            Source::Command(command) => {
+                let command = command.clone();
+                self.spawn(&command, events);
+            }
+        }
+        self.interval
+    }
+
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        let output = self.output.borrow();
+        if output.text.is_empty() {
+            self.section = None;
+            return (0.0, 0.0);
+        }
+        let style = output
+            .class
+            .as_ref()
+            .and_then(|class| self.class_styles.get(class))
+            .unwrap_or(&self.style);
+        let section = TextSection {
+            text: output.text.clone(),
+            style: style.clone(),
+            position: (0.0, 0.0),
+        };
+        let size = frame.text.measure(&section, frame.scale_factor as f32);
+        self.section = Some((section, size));
+        size
+    }
+
+    fn draw(
+        &mut self,
+        _frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        if let Some((mut section, _)) = self.section.take() {
+            section.position = (align_x(section.style.align, origin.0, size.0), origin.1);
+            overlay.texts.push(section);
+        }
+    }
+}
+
+/// Styles for output classes, from keys such as `critical_color`,
+/// `critical_font` and `critical_size`. Anything a class doesn't set comes
+/// from `base`.
+fn class_styles(conf: &Config, section: &str, base: &TextStyle) -> HashMap<String, TextStyle> {
+    let mut styles: HashMap<String, TextStyle> = HashMap::new();
+    let Some(properties) = conf.section(section) else {
+        return styles;
+    };
+    for (key, raw) in properties.iter() {
+        let Some((class, attribute)) = key.rsplit_once('_') else {
+            continue;
+        };
+        if class.is_empty() {
+            continue;
+        }
+        let style = match attribute {
+            "color" | "font" | "size" => styles
+                .entry(class.to_string())
+                .or_insert_with(|| base.clone()),
+            _ => continue,
+        };
+        match attribute {
+            "color" => style.color = color(conf, section, key, base.color),
+            "font" => style.font = raw.to_string(),
+            _ => style.size = raw.parse().unwrap_or(base.size),
+        }
+    }
+    styles
+}
+
+/// Turns command or file output into what the widget shows.
+fn parse(raw: &str, format: Format) -> Output {
+    let raw = raw.trim();
+    let as_json = match format {
+        Format::Json => true,
+        Format::Auto => raw.starts_with('{'),
+        Format::Text => false,
+    };
+    if as_json {
+        // Tools that keep printing write one object per line; the last one
+        // is the current state.
+        let last = raw.lines().rev().find(|line| !line.trim().is_empty());
+        let object = json::object(raw).or_else(|| last.and_then(json::object));
+        match object {
+            Some(mut fields) => {
+                return Output {
+                    text: fields.remove("text").unwrap_or_default(),
+                    class: fields.remove("class"),
+                }
+            }
+            None if format == Format::Json => {
+                tracing::warn!("Command output is not a JSON object: {}", raw);
+                return Output::default();
+            }
+            None => {}
+        }
+    }
+    Output {
+        text: raw.to_string(),
+        class: None,
+    }
+}
+
+/// Just enough JSON to read the top-level fields of an object. Strings are
+/// unescaped, other scalars keep their source text and nested values are
+/// skipped.
+mod json {
+    use std::{collections::HashMap, iter::Peekable, str::Chars};
+
+    pub fn object(input: &str) -> Option<HashMap<String, String>> {
+        let mut chars = input.trim().chars().peekable();
+        let mut fields = HashMap::new();
+        expect(&mut chars, '{')?;
+        skip_whitespace(&mut chars);
+        if chars.peek() == Some(&'}') {
+            chars.next();
+            return Some(fields);
+        }
+        loop {
+            skip_whitespace(&mut chars);
+            let key = string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            expect(&mut chars, ':')?;
+            skip_whitespace(&mut chars);
+            if let Some(value) = value(&mut chars)? {
+                fields.insert(key, value);
+            }
+            skip_whitespace(&mut chars);
+            match chars.next()? {
+                ',' => continue,
+                '}' => break,
+                _ => return None,
+            }
+        }
+        skip_whitespace(&mut chars);
+        chars.next().is_none().then_some(fields)
+    }
+
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    fn expect(chars: &mut Peekable<Chars>, expected: char) -> Option<()> {
+        (chars.next()? == expected).then_some(())
+    }
+
+    fn string(chars: &mut Peekable<Chars>) -> Option<String> {
+        expect(chars, '"')?;
+        let mut out = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(out),
+                '\\' => match chars.next()? {
+                    'n' => out.push('\n'),
+                    't' => out.push('\t'),
+                    'r' => out.push('\r'),
+                    'b' => out.push('\u{8}'),
+                    'f' => out.push('\u{c}'),
+                    'u' => {
+                        let high = hex4(chars)?;
+                        let code = if (0xD800..0xDC00).contains(&high) {
+                            expect(chars, '\\')?;
+                            expect(chars, 'u')?;
+                            let low = hex4(chars)?;
+                            if !(0xDC00..0xE000).contains(&low) {
+                                return None;
+                            }
+                            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                        } else {
+                            high
+                        };
+                        out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                    }
+                    other => out.push(other),
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn hex4(chars: &mut Peekable<Chars>) -> Option<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            code = code * 16 + chars.next()?.to_digit(16)?;
+        }
+        Some(code)
+    }
+
+    /// Reads one value. Strings and scalars come back as text, objects and
+    /// arrays as `None`.
+    fn value(chars: &mut Peekable<Chars>) -> Option<Option<String>> {
+        match *chars.peek()? {
+            '"' => Some(Some(string(chars)?)),
+            '{' | '[' => {
+                skip_nested(chars)?;
+                Some(None)
+            }
+            _ => {
+                let mut scalar = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == ',' || c == '}' || c == ']' || c.is_whitespace() {
+                        break;
+                    }
+                    scalar.push(c);
+                    chars.next();
+                }
+                (!scalar.is_empty()).then_some(Some(scalar))
+            }
+        }
+    }
+
+    fn skip_nested(chars: &mut Peekable<Chars>) -> Option<()> {
+        let mut depth = 0;
+        loop {
+            match *chars.peek()? {
+                '"' => {
+                    string(chars)?;
+                    continue;
+                }
+                '{' | '[' => depth += 1,
+                '}' | ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        chars.next();
+                        return Some(());
+                    }
+                }
+                _ => {}
+            }
+            chars.next();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::object;
+
+        fn field(input: &str, key: &str) -> Option<String> {
+            object(input)?.remove(key)
+        }
+
+        #[test]
+        fn reads_fields() {
+            let fields = object(r#"{"text": "50%", "class": "low", "percentage": 50}"#).unwrap();
+            assert_eq!(fields["text"], "50%");
+            assert_eq!(fields["class"], "low");
+            assert_eq!(fields["percentage"], "50");
+            assert!(object("{}").unwrap().is_empty());
+            assert!(object(" {\n} \n").unwrap().is_empty());
+        }
+
+        #[test]
+        fn unescapes_strings() {
+            assert_eq!(
+                field(r#"{"text": "a\"b\\c\/d\n\t\r\b\f"}"#, "text").unwrap(),
+                "a\"b\\c/d\n\t\r\u{8}\u{c}"
+            );
+            assert_eq!(field(r#"{"text": "\u00e9\u2603"}"#, "text").unwrap(), "é☃");
+            assert_eq!(field(r#"{"t\u0065xt": "key"}"#, "text").unwrap(), "key");
+        }
+
+        #[test]
+        fn joins_surrogate_pairs() {
+            assert_eq!(field(r#"{"text": "\ud83d\udd0b"}"#, "text").unwrap(), "🔋");
+            // A lone low surrogate is not a character.
+            assert_eq!(field(r#"{"text": "\udd0b"}"#, "text").unwrap(), "\u{FFFD}");
+            assert!(object(r#"{"text": "\ud83d"}"#).is_none());
+            assert!(object(r#"{"text": "\ud83dx"}"#).is_none());
+            assert!(object(r#"{"text": "\ud83d\u0041"}"#).is_none());
+        }
+
+        #[test]
+        fn skips_nested_values() {
+            let fields = object(
+                r#"{"tooltip": {"lines": ["a", "}", {"b": "]"}]}, "alt": [1, [2]], "text": "ok"}"#,
+            )
+            .unwrap();
+            assert_eq!(fields.len(), 1);
+            assert_eq!(fields["text"], "ok");
+        }
+
+        #[test]
+        fn rejects_malformed_input() {
+            for input in [
+                "",
+                "text",
+                "[\"text\"]",
+                "{",
+                r#"{"text"}"#,
+                r#"{"text": }"#,
+                r#"{"text": "open}"#,
+                r#"{"text": "a" "class": "b"}"#,
+                r#"{"text": "a",}"#,
+                r#"{text: "a"}"#,
+                r#"{"text": "a"} trailing"#,
+                r#"{"tooltip": {"a": [1, 2}"#,
+                r#"{"text": "\u12g4"}"#,
+            ] {
+                assert!(object(input).is_none(), "accepted {:?}", input);
+            }
+        }
+    }
+}
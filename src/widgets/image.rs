@@ -0,0 +1,134 @@
+use std::{collections::HashMap, rc::Rc};
+
+use image::RgbaImage;
+
+use crate::config::Config;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::sprites::Sprite;
+use crate::media::image_media::{decode, shrink};
+
+use super::{value, Anchor, Frame, Widget};
+
+pub const ANCHOR_DEFAULT: Anchor = Anchor::Center;
+pub const OFFSET_DEFAULT: (f32, f32) = (0.0, 0.0);
+
+/// A picture such as a logo, drawn at `width` × `height` logical pixels.
+pub struct ImageWidget {
+    image: RgbaImage,
+    /// The image shrunk for each output scale factor it was drawn at.
+    scaled: HashMap<u32, Rc<RgbaImage>>,
+    size: (f32, f32),
+    opacity: f32,
+}
+
+impl ImageWidget {
+    /// Loads `path` from `section`. A missing or unreadable file only drops
+    /// the widget, the lock screen still comes up.
+    pub fn from_config(conf: &Config, section: &str) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        let path = match conf.get_path(section, "path") {
+            Some(Ok(path)) => path,
+            Some(Err(e)) => {
+                tracing::warn!("Skipping [{}]: {}", section, e);
+                return None;
+            }
+            None => {
+                tracing::warn!("Skipping [{}]: no path given", section);
+                return None;
+            }
+        };
+        let image = match decode(&path, (0, 0)) {
+            Ok(image) => image,
+            Err(e) => {
+                tracing::warn!("Skipping [{}]: unable to open {:?}, {}", section, path, e);
+                return None;
+            }
+        };
+
+        let natural = (image.width() as f32, image.height() as f32);
+        let width = conf
+            .get(section, "width")
+            .and_then(|v| v.parse::<f32>().ok());
+        let height = conf
+            .get(section, "height")
+            .and_then(|v| v.parse::<f32>().ok());
+        let size = match (width, height) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, w * natural.1 / natural.0),
+            (None, Some(h)) => (h * natural.0 / natural.1, h),
+            (None, None) => natural,
+        };
+        Some(Self {
+            image,
+            scaled: HashMap::new(),
+            size,
+            opacity: value(conf, section, "opacity", 1.0f32).clamp(0.0, 1.0),
+        })
+    }
+
+    /// The image at about the size it is drawn on outputs with
+    /// `scale_factor`, so no more is uploaded than shown.
+    fn scaled(&mut self, scale_factor: u32) -> Rc<RgbaImage> {
+        let image = &self.image;
+        let size = (
+            (self.size.0 * scale_factor as f32).ceil() as u32,
+            (self.size.1 * scale_factor as f32).ceil() as u32,
+        );
+        self.scaled
+            .entry(scale_factor)
+            .or_insert_with(|| Rc::new(shrink(image.clone(), size)))
+            .clone()
+    }
+}
+
+impl Widget for ImageWidget {
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        (frame.scaled(self.size.0), frame.scaled(self.size.1))
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        overlay.sprites.push(Sprite {
+            image: self.scaled(frame.scale_factor),
+            position: origin,
+            size,
+            opacity: self.opacity,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shrinks_oversized_images_for_each_scale() {
+        let mut widget = ImageWidget {
+            image: RgbaImage::new(2048, 1024),
+            scaled: HashMap::new(),
+            size: (64.0, 32.0),
+            opacity: 1.0,
+        };
+        assert_eq!(widget.scaled(1).dimensions(), (64, 32));
+        assert_eq!(widget.scaled(2).dimensions(), (128, 64));
+        assert!(Rc::ptr_eq(&widget.scaled(2), &widget.scaled(2)));
+    }
+
+    #[test]
+    fn keeps_small_images() {
+        let mut widget = ImageWidget {
+            image: RgbaImage::new(48, 48),
+            scaled: HashMap::new(),
+            size: (64.0, 64.0),
+            opacity: 1.0,
+        };
+        assert_eq!(widget.scaled(2).dimensions(), (48, 48));
+    }
+}
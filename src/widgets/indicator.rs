@@ -0,0 +1,263 @@
+use std::{f32::consts::TAU, str::FromStr};
+
+use crate::config::Config;
+use crate::graphics::overlay::{Overlay, Target};
+use crate::graphics::shapes::Shape;
+use crate::graphics::text::{Align, TextSection, TextStyle};
+use crate::lock::AuthState;
+
+use super::{color, text_style, value, Action, Anchor, Frame, LockView, Widget};
+
+const RADIUS_DEFAULT: f32 = 50.0;
+const THICKNESS_DEFAULT: f32 = 10.0;
+const DOT_RADIUS_DEFAULT: f32 = 6.0;
+const DOT_SPACING_DEFAULT: f32 = 8.0;
+const MAX_DOTS_DEFAULT: usize = 16;
+const REVEAL_SIZE_DEFAULT: f32 = 16.0;
+pub const ANCHOR_DEFAULT: Anchor = Anchor::Bottom;
+pub const OFFSET_DEFAULT: (f32, f32) = (0.0, -80.0);
+/// Length of the highlighted arc shown on key presses.
+const HIGHLIGHT_SPAN: f32 = TAU / 6.0;
+/// Each key press moves the highlight on by roughly the golden angle so
+/// consecutive presses land in visibly different places.
+const HIGHLIGHT_STEP: f32 = 2.4;
+
+const RING_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.2,
+    g: 0.2,
+    b: 0.2,
+    a: 0.75,
+};
+const TYPING_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.2,
+    g: 0.6,
+    b: 1.0,
+    a: 1.0,
+};
+const CLEAR_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.9,
+    g: 0.6,
+    b: 0.2,
+    a: 1.0,
+};
+const VERIFYING_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.6,
+    g: 0.4,
+    b: 0.9,
+    a: 1.0,
+};
+const WRONG_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.9,
+    g: 0.2,
+    b: 0.2,
+    a: 1.0,
+};
+const CAPS_LOCK_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.9,
+    g: 0.8,
+    b: 0.2,
+    a: 1.0,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndicatorStyle {
+    Ring,
+    Dots,
+}
+
+impl FromStr for IndicatorStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ring" => Ok(IndicatorStyle::Ring),
+            "dots" => Ok(IndicatorStyle::Dots),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Ring or row of dots showing password entry and the result of the last
+/// attempt.
+pub struct Indicator {
+    style: IndicatorStyle,
+    radius: f32,
+    thickness: f32,
+    dot_radius: f32,
+    dot_spacing: f32,
+    max_dots: usize,
+    idle_visible: bool,
+    ring_color: wgpu::Color,
+    typing_color: wgpu::Color,
+    clear_color: wgpu::Color,
+    verifying_color: wgpu::Color,
+    wrong_color: wgpu::Color,
+    caps_lock_color: wgpu::Color,
+    click_to_reveal: bool,
+    reveal_style: TextStyle,
+    /// The password as text while it is revealed, and its size.
+    revealed: Option<(TextSection, (f32, f32))>,
+}
+
+impl Indicator {
+    /// Builds an indicator from `section`; it is shown unless
+    /// `enabled=false`. With `click_to_reveal=true` clicking it shows the
+    /// password in clear text until clicked again.
+    pub fn from_config(conf: &Config, section: &str) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        let style = conf
+            .get(section, "style")
+            .map(|s| {
+                s.parse::<IndicatorStyle>().unwrap_or_else(|_| {
+                    tracing::warn!("Unknown indicator style '{}', expected 'ring' or 'dots'", s);
+                    IndicatorStyle::Ring
+                })
+            })
+            .unwrap_or(IndicatorStyle::Ring);
+        Some(Self {
+            style,
+            radius: value(conf, section, "radius", RADIUS_DEFAULT),
+            thickness: value(conf, section, "thickness", THICKNESS_DEFAULT),
+            dot_radius: value(conf, section, "dot_radius", DOT_RADIUS_DEFAULT),
+            dot_spacing: value(conf, section, "dot_spacing", DOT_SPACING_DEFAULT),
+            max_dots: value(conf, section, "max_dots", MAX_DOTS_DEFAULT).max(1),
+            idle_visible: value(conf, section, "idle_visible", false),
+            ring_color: color(conf, section, "ring_color", RING_COLOR_DEFAULT),
+            typing_color: color(conf, section, "typing_color", TYPING_COLOR_DEFAULT),
+            clear_color: color(conf, section, "clear_color", CLEAR_COLOR_DEFAULT),
+            verifying_color: color(conf, section, "verifying_color", VERIFYING_COLOR_DEFAULT),
+            wrong_color: color(conf, section, "wrong_color", WRONG_COLOR_DEFAULT),
+            caps_lock_color: color(conf, section, "caps_lock_color", CAPS_LOCK_COLOR_DEFAULT),
+            click_to_reveal: value(conf, section, "click_to_reveal", false),
+            reveal_style: text_style(conf, section, "reveal_", REVEAL_SIZE_DEFAULT, Align::Center),
+            revealed: None,
+        })
+    }
+
+    /// Color of the ring, or of the dots when nothing is being typed.
+    fn base_color(&self, state: AuthState, caps_lock: bool) -> wgpu::Color {
+        match state {
+            AuthState::Verifying => self.verifying_color,
+            AuthState::Wrong => self.wrong_color,
+            _ if caps_lock => self.caps_lock_color,
+            _ => self.ring_color,
+        }
+    }
+
+    /// Whether anything is shown for the current state.
+    fn visible(&self, lock: &LockView) -> bool {
+        lock.auth_state != AuthState::Idle
+            || lock.password_length > 0
+            || lock.caps_lock
+            || self.idle_visible
+    }
+}
+
+impl Widget for Indicator {
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        if !self.visible(frame.lock) {
+            return (0.0, 0.0);
+        }
+        self.revealed = frame
+            .lock
+            .revealed
+            .filter(|password| !password.is_empty())
+            .map(|password| {
+                let section = TextSection {
+                    text: password.to_string(),
+                    style: self.reveal_style.clone(),
+                    position: (0.0, 0.0),
+                };
+                let size = frame.text.measure(&section, frame.scale_factor as f32);
+                (section, size)
+            });
+        match self.style {
+            IndicatorStyle::Ring => {
+                let diameter = frame.scaled(self.radius * 2.0);
+                let text = self.revealed.as_ref().map_or((0.0, 0.0), |(_, s)| *s);
+                (diameter.max(text.0), diameter.max(text.1))
+            }
+            // The password takes the place of the dots.
+            IndicatorStyle::Dots if self.revealed.is_some() => self.revealed.as_ref().unwrap().1,
+            IndicatorStyle::Dots => {
+                let count = frame.lock.password_length.clamp(1, self.max_dots) as f32;
+                let diameter = frame.scaled(self.dot_radius * 2.0);
+                let spacing = frame.scaled(self.dot_spacing);
+                (count * diameter + (count - 1.0) * spacing, diameter)
+            }
+        }
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        let lock = frame.lock;
+        let base_color = self.base_color(lock.auth_state, lock.caps_lock);
+        let center = (origin.0 + size.0 / 2.0, origin.1 + size.1 / 2.0);
+        if self.click_to_reveal {
+            overlay.targets.push(Target {
+                position: origin,
+                size,
+                action: Action::ToggleReveal,
+            });
+        }
+        if let Some((mut section, text_size)) = self.revealed.take() {
+            section.position = (center.0, center.1 - text_size.1 / 2.0);
+            overlay.texts.push(section);
+            if self.style == IndicatorStyle::Dots {
+                return;
+            }
+        }
+
+        match self.style {
+            IndicatorStyle::Ring => {
+                let outer_radius = frame.scaled(self.radius);
+                let inner_radius = frame.scaled((self.radius - self.thickness).max(0.0));
+                overlay.shapes.push(Shape::Ring {
+                    center,
+                    inner_radius,
+                    outer_radius,
+                    start_angle: 0.0,
+                    end_angle: TAU,
+                    color: base_color,
+                });
+                let highlight = match lock.auth_state {
+                    AuthState::Typing => Some(self.typing_color),
+                    AuthState::Clearing => Some(self.clear_color),
+                    _ => None,
+                };
+                if let Some(color) = highlight {
+                    let start_angle = (lock.password_length as f32 * HIGHLIGHT_STEP) % TAU;
+                    overlay.shapes.push(Shape::Ring {
+                        center,
+                        inner_radius,
+                        outer_radius,
+                        start_angle,
+                        end_angle: start_angle + HIGHLIGHT_SPAN,
+                        color,
+                    });
+                }
+            }
+            IndicatorStyle::Dots => {
+                let count = lock.password_length.clamp(1, self.max_dots);
+                let radius = frame.scaled(self.dot_radius);
+                let spacing = frame.scaled(self.dot_spacing);
+                let color = match lock.auth_state {
+                    AuthState::Typing => self.typing_color,
+                    AuthState::Clearing => self.clear_color,
+                    _ => base_color,
+                };
+                overlay.shapes.extend((0..count).map(|i| {
+                    let x = origin.0 + radius + i as f32 * (radius * 2.0 + spacing);
+                    Shape::circle((x, center.1), radius, color)
+                }));
+            }
+        }
+    }
+}
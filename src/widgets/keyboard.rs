@@ -0,0 +1,152 @@
+use crate::config::Config;
+use crate::graphics::overlay::{Overlay, Target};
+use crate::graphics::text::{Align, TextSection, TextStyle};
+
+use super::{color, text_style, value, Action, Anchor, Frame, Widget};
+
+const SIZE_DEFAULT: f32 = 14.0;
+const SPACING_DEFAULT: f32 = 16.0;
+const CAPS_LOCK_TEXT_DEFAULT: &str = "Caps Lock";
+const NUM_LOCK_TEXT_DEFAULT: &str = "Num Lock";
+const WARNING_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.9,
+    g: 0.8,
+    b: 0.2,
+    a: 1.0,
+};
+pub const ANCHOR_DEFAULT: Anchor = Anchor::Bottom;
+pub const OFFSET_DEFAULT: (f32, f32) = (0.0, -40.0);
+
+/// When the active layout is shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ShowLayout {
+    Always,
+    /// Only when the keymap has more than one layout.
+    Auto,
+    Never,
+}
+
+/// Caps lock and num lock warnings next to the active keyboard layout,
+/// laid out in a row.
+pub struct Keyboard {
+    caps_lock_text: Option<String>,
+    num_lock_text: Option<String>,
+    show_layout: ShowLayout,
+    style: TextStyle,
+    warning_style: TextStyle,
+    spacing: f32,
+    sections: Vec<(TextSection, (f32, f32))>,
+    /// Which section is the layout, when clicking it can switch to another.
+    switcher: Option<usize>,
+}
+
+impl Keyboard {
+    /// Builds the widget from `section`. `caps_lock_text` and
+    /// `num_lock_text` replace the warnings, and an empty one hides it.
+    pub fn from_config(conf: &Config, section: &str, anchor: Anchor) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        let text = |key: &str, default: &str| {
+            Some(conf.get(section, key).unwrap_or(default).to_string()).filter(|t| !t.is_empty())
+        };
+        let show_layout = match conf.get(section, "show_layout") {
+            None | Some("auto") => ShowLayout::Auto,
+            Some("true") => ShowLayout::Always,
+            Some("false") => ShowLayout::Never,
+            Some(other) => {
+                tracing::warn!(
+                    "Invalid [{}] show_layout '{}', expected auto, true or false",
+                    section,
+                    other
+                );
+                ShowLayout::Auto
+            }
+        };
+        let style = text_style(conf, section, "", SIZE_DEFAULT, anchor.align());
+        let mut warning_style = style.clone();
+        warning_style.color = color(conf, section, "warning_color", WARNING_COLOR_DEFAULT);
+        Some(Self {
+            caps_lock_text: text("caps_lock_text", CAPS_LOCK_TEXT_DEFAULT),
+            num_lock_text: text("num_lock_text", NUM_LOCK_TEXT_DEFAULT),
+            show_layout,
+            style,
+            warning_style,
+            spacing: value(conf, section, "spacing", SPACING_DEFAULT),
+            sections: Vec::new(),
+            switcher: None,
+        })
+    }
+}
+
+impl Widget for Keyboard {
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        let lock = frame.lock;
+        let mut pieces: Vec<(String, &TextStyle)> = Vec::new();
+        if let Some(text) = self.caps_lock_text.as_ref().filter(|_| lock.caps_lock) {
+            pieces.push((text.clone(), &self.warning_style));
+        }
+        if let Some(text) = self.num_lock_text.as_ref().filter(|_| lock.num_lock) {
+            pieces.push((text.clone(), &self.warning_style));
+        }
+        let show_layout = match self.show_layout {
+            ShowLayout::Always => true,
+            ShowLayout::Auto => lock.layouts > 1,
+            ShowLayout::Never => false,
+        };
+        self.switcher = None;
+        if let Some(layout) = lock.layout.filter(|_| show_layout) {
+            if lock.layouts > 1 {
+                self.switcher = Some(pieces.len());
+            }
+            pieces.push((layout.to_string(), &self.style));
+        }
+
+        let scale = frame.scale_factor as f32;
+        self.sections = pieces
+            .into_iter()
+            .map(|(text, style)| {
+                let mut style = style.clone();
+                // Pieces are placed one after the other from the left.
+                style.align = Align::Left;
+                let section = TextSection {
+                    text,
+                    style,
+                    position: (0.0, 0.0),
+                };
+                let size = frame.text.measure(&section, scale);
+                (section, size)
+            })
+            .collect();
+        if self.sections.is_empty() {
+            return (0.0, 0.0);
+        }
+        let gaps = frame.scaled(self.spacing) * (self.sections.len() - 1) as f32;
+        self.sections
+            .iter()
+            .fold((gaps, 0.0f32), |acc, (_, s)| (acc.0 + s.0, acc.1.max(s.1)))
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        let spacing = frame.scaled(self.spacing);
+        let mut x = origin.0;
+        for (index, (mut section, piece)) in self.sections.drain(..).enumerate() {
+            section.position = (x, origin.1 + (size.1 - piece.1) / 2.0);
+            if self.switcher == Some(index) {
+                overlay.targets.push(Target {
+                    position: section.position,
+                    size: piece,
+                    action: Action::CycleLayout,
+                });
+            }
+            x += piece.0 + spacing;
+            overlay.texts.push(section);
+        }
+    }
+}
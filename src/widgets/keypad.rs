@@ -0,0 +1,288 @@
+use std::str::FromStr;
+
+use smithay_client_toolkit::seat::keyboard::Keysym;
+
+use crate::config::Config;
+use crate::graphics::overlay::{Overlay, Target};
+use crate::graphics::shapes::Shape;
+use crate::graphics::text::{Align, TextSection, TextStyle};
+
+use super::{color, text_style, value, Action, Anchor, Frame, LockView, Widget};
+
+const PIN_KEY_SIZE_DEFAULT: f32 = 64.0;
+const FULL_KEY_SIZE_DEFAULT: f32 = 40.0;
+const SPACING_DEFAULT: f32 = 8.0;
+const CORNER_RADIUS_DEFAULT: f32 = 8.0;
+const LABEL_SIZE_DEFAULT: f32 = 20.0;
+const BACKSPACE_TEXT_DEFAULT: &str = "Del";
+const ENTER_TEXT_DEFAULT: &str = "OK";
+const SHIFT_TEXT_DEFAULT: &str = "Shift";
+const KEY_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.2,
+    g: 0.2,
+    b: 0.2,
+    a: 0.75,
+};
+const ACTIVE_COLOR_DEFAULT: wgpu::Color = wgpu::Color {
+    r: 0.2,
+    g: 0.6,
+    b: 1.0,
+    a: 0.75,
+};
+// Off to the side, clear of the clock, indicator and messages.
+pub const ANCHOR_DEFAULT: Anchor = Anchor::Right;
+pub const OFFSET_DEFAULT: (f32, f32) = (-40.0, 0.0);
+
+const PIN_ROWS: [&str; 3] = ["123", "456", "789"];
+const FULL_ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+const DIGITS: &str = "1234567890";
+const SHIFTED_DIGITS: &str = "!@#$%^&*()";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeypadStyle {
+    /// Digits only, for PINs.
+    Pin,
+    /// Letters, digits and the symbols above them.
+    Full,
+}
+
+impl FromStr for KeypadStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pin" => Ok(KeypadStyle::Pin),
+            "full" => Ok(KeypadStyle::Full),
+            _ => Err(()),
+        }
+    }
+}
+
+/// When the keypad is shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Show {
+    Always,
+    /// When there is no keyboard or the screen has been touched.
+    Auto,
+    /// Once the screen has been touched.
+    Touch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cap {
+    Char(char),
+    Backspace,
+    Enter,
+    Shift,
+    Space,
+}
+
+impl Cap {
+    fn action(&self) -> Action {
+        match self {
+            Cap::Char(c) => Action::Key(Keysym::from_char(*c)),
+            Cap::Backspace => Action::Key(Keysym::BackSpace),
+            Cap::Enter => Action::Key(Keysym::Return),
+            Cap::Shift => Action::Shift,
+            Cap::Space => Action::Key(Keysym::space),
+        }
+    }
+}
+
+/// A key and how many key widths it takes.
+type Key = (Cap, f32);
+
+/// On-screen keys for touchscreens. Taps go into the password like key
+/// presses.
+pub struct Keypad {
+    style: KeypadStyle,
+    show: Show,
+    key_size: f32,
+    spacing: f32,
+    corner_radius: f32,
+    key_color: wgpu::Color,
+    active_color: wgpu::Color,
+    label_style: TextStyle,
+    backspace_text: String,
+    enter_text: String,
+    shift_text: String,
+    rows: Vec<Vec<Key>>,
+    shift: bool,
+}
+
+impl Keypad {
+    /// Builds the keypad from `section`. `style` is `pin` or `full`, and
+    /// `show` is `auto`, `touch` or `always`.
+    pub fn from_config(conf: &Config, section: &str) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        let style = conf
+            .get(section, "style")
+            .map(|s| {
+                s.parse::<KeypadStyle>().unwrap_or_else(|_| {
+                    tracing::warn!("Unknown keypad style '{}', expected 'pin' or 'full'", s);
+                    KeypadStyle::Pin
+                })
+            })
+            .unwrap_or(KeypadStyle::Pin);
+        let show = match conf.get(section, "show") {
+            None | Some("auto") => Show::Auto,
+            Some("touch") => Show::Touch,
+            Some("always") => Show::Always,
+            Some(other) => {
+                tracing::warn!(
+                    "Invalid [{}] show '{}', expected auto, touch or always",
+                    section,
+                    other
+                );
+                Show::Auto
+            }
+        };
+        let key_size_default = match style {
+            KeypadStyle::Pin => PIN_KEY_SIZE_DEFAULT,
+            KeypadStyle::Full => FULL_KEY_SIZE_DEFAULT,
+        };
+        let text = |key: &str, default: &str| conf.get(section, key).unwrap_or(default).to_string();
+        Some(Self {
+            style,
+            show,
+            key_size: value(conf, section, "key_size", key_size_default),
+            spacing: value(conf, section, "spacing", SPACING_DEFAULT),
+            corner_radius: value(conf, section, "corner_radius", CORNER_RADIUS_DEFAULT),
+            key_color: color(conf, section, "key_color", KEY_COLOR_DEFAULT),
+            active_color: color(conf, section, "active_color", ACTIVE_COLOR_DEFAULT),
+            label_style: text_style(conf, section, "", LABEL_SIZE_DEFAULT, Align::Center),
+            backspace_text: text("backspace_text", BACKSPACE_TEXT_DEFAULT),
+            enter_text: text("enter_text", ENTER_TEXT_DEFAULT),
+            shift_text: text("shift_text", SHIFT_TEXT_DEFAULT),
+            rows: Vec::new(),
+            shift: false,
+        })
+    }
+
+    fn visible(&self, lock: &LockView) -> bool {
+        match self.show {
+            Show::Always => true,
+            Show::Auto => !lock.physical_keyboard || lock.touched,
+            Show::Touch => lock.touched,
+        }
+    }
+
+    fn rows(&self, shift: bool) -> Vec<Vec<Key>> {
+        let chars = |row: &str| -> Vec<Key> { row.chars().map(|c| (Cap::Char(c), 1.0)).collect() };
+        match self.style {
+            KeypadStyle::Pin => {
+                let mut rows: Vec<Vec<Key>> = PIN_ROWS.iter().map(|row| chars(row)).collect();
+                rows.push(vec![
+                    (Cap::Backspace, 1.0),
+                    (Cap::Char('0'), 1.0),
+                    (Cap::Enter, 1.0),
+                ]);
+                rows
+            }
+            KeypadStyle::Full => {
+                let case = |row: &str| {
+                    if shift {
+                        row.to_uppercase()
+                    } else {
+                        row.to_string()
+                    }
+                };
+                let mut rows = vec![chars(if shift { SHIFTED_DIGITS } else { DIGITS })];
+                rows.push(chars(&case(FULL_ROWS[0])));
+                rows.push(chars(&case(FULL_ROWS[1])));
+                let mut bottom = vec![(Cap::Shift, 1.5)];
+                bottom.extend(chars(&case(FULL_ROWS[2])));
+                bottom.push((Cap::Backspace, 1.5));
+                rows.push(bottom);
+                rows.push(vec![(Cap::Space, 6.0), (Cap::Enter, 2.0)]);
+                rows
+            }
+        }
+    }
+
+    fn label(&self, cap: Cap) -> String {
+        match cap {
+            Cap::Char(c) => c.to_string(),
+            Cap::Backspace => self.backspace_text.clone(),
+            Cap::Enter => self.enter_text.clone(),
+            Cap::Shift => self.shift_text.clone(),
+            Cap::Space => String::new(),
+        }
+    }
+
+    /// Width of a row in physical pixels.
+    fn row_width(&self, row: &[Key], key: f32, spacing: f32) -> f32 {
+        let units: f32 = row.iter().map(|(_, width)| width).sum();
+        // Wide keys also cover the gaps they span.
+        units * key + (units - 1.0) * spacing
+    }
+}
+
+impl Widget for Keypad {
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        if !self.visible(frame.lock) {
+            self.rows.clear();
+            return (0.0, 0.0);
+        }
+        self.shift = frame.lock.keypad_shift;
+        self.rows = self.rows(self.shift);
+        let key = frame.scaled(self.key_size);
+        let spacing = frame.scaled(self.spacing);
+        let width = self
+            .rows
+            .iter()
+            .fold(0.0f32, |w, row| w.max(self.row_width(row, key, spacing)));
+        let count = self.rows.len() as f32;
+        (width, count * key + (count - 1.0) * spacing)
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        let key = frame.scaled(self.key_size);
+        let spacing = frame.scaled(self.spacing);
+        let radius = frame.scaled(self.corner_radius);
+        let scale = frame.scale_factor as f32;
+        let mut y = origin.1;
+        for row in std::mem::take(&mut self.rows) {
+            let mut x = origin.0 + (size.0 - self.row_width(&row, key, spacing)) / 2.0;
+            for (cap, units) in row {
+                let width = units * key + (units - 1.0) * spacing;
+                let active = cap == Cap::Shift && self.shift;
+                overlay.shapes.push(Shape::Rect {
+                    position: (x, y),
+                    size: (width, key),
+                    corner_radius: radius,
+                    color: if active {
+                        self.active_color
+                    } else {
+                        self.key_color
+                    },
+                });
+                overlay.targets.push(Target {
+                    position: (x, y),
+                    size: (width, key),
+                    action: cap.action(),
+                });
+                let mut section = TextSection {
+                    text: self.label(cap),
+                    style: self.label_style.clone(),
+                    position: (0.0, 0.0),
+                };
+                if !section.text.is_empty() {
+                    let (_, height) = frame.text.measure(&section, scale);
+                    section.position = (x + width / 2.0, y + (key - height) / 2.0);
+                    overlay.texts.push(section);
+                }
+                x += width + spacing;
+            }
+            y += key + spacing;
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::time::Duration;
+
+use smithay_client_toolkit::{reexports::calloop::LoopHandle, seat::keyboard::Keysym};
+
+use crate::app::AppData;
+use crate::config::Config;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::text::Align;
+
+use super::{
+    battery::{self, Battery},
+    clock::{self, Clock},
+    command::{self, CommandWidget},
+    image::{self, ImageWidget},
+    indicator::{self, Indicator},
+    keyboard::{self, Keyboard},
+    keypad::{self, Keypad},
+    message::{self, Message},
+    now_playing::{self, NowPlaying},
+    placement,
+    user::{self, User},
+    value, Anchor, Frame, Widget,
+};
+
+const LAYOUT_SECTION: &str = "layout";
+const SPACING_DEFAULT: f32 = 8.0;
+
+/// Shortest of two optional update intervals.
+fn sooner(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    Column,
+    Row,
+}
+
+/// Widgets stacked top to bottom or left to right. Columns line their
+/// children up along the group's anchored edge, rows center them
+/// vertically.
+struct Group {
+    direction: Direction,
+    spacing: f32,
+    align: Align,
+    children: Vec<Box<dyn Widget>>,
+    sizes: Vec<(f32, f32)>,
+}
+
+impl Widget for Group {
+    fn update(&mut self, events: &LoopHandle<'static, AppData>) -> Option<Duration> {
+        self.children
+            .iter_mut()
+            .fold(None, |next, child| sooner(next, child.update(events)))
+    }
+
+    fn key(&mut self, keysym: Keysym) -> bool {
+        self.children.iter_mut().any(|child| child.key(keysym))
+    }
+
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        self.sizes = self
+            .children
+            .iter_mut()
+            .map(|child| child.layout(frame))
+            .collect();
+        let visible: Vec<&(f32, f32)> = self
+            .sizes
+            .iter()
+            .filter(|s| s.0 > 0.0 && s.1 > 0.0)
+            .collect();
+        if visible.is_empty() {
+            return (0.0, 0.0);
+        }
+        let gaps = frame.scaled(self.spacing) * (visible.len() - 1) as f32;
+        match self.direction {
+            Direction::Column => (
+                visible.iter().fold(0.0f32, |w, s| w.max(s.0)),
+                visible.iter().map(|s| s.1).sum::<f32>() + gaps,
+            ),
+            Direction::Row => (
+                visible.iter().map(|s| s.0).sum::<f32>() + gaps,
+                visible.iter().fold(0.0f32, |h, s| h.max(s.1)),
+            ),
+        }
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        let spacing = frame.scaled(self.spacing);
+        let mut pen = origin;
+        for (child, child_size) in self.children.iter_mut().zip(self.sizes.clone()) {
+            if child_size.0 <= 0.0 || child_size.1 <= 0.0 {
+                continue;
+            }
+            match self.direction {
+                Direction::Column => {
+                    let x = match self.align {
+                        Align::Left => origin.0,
+                        Align::Center => origin.0 + (size.0 - child_size.0) / 2.0,
+                        Align::Right => origin.0 + size.0 - child_size.0,
+                    };
+                    child.draw(frame, (x, pen.1), child_size, overlay);
+                    pen.1 += child_size.1 + spacing;
+                }
+                Direction::Row => {
+                    let y = origin.1 + (size.1 - child_size.1) / 2.0;
+                    child.draw(frame, (pen.0, y), child_size, overlay);
+                    pen.0 += child_size.0 + spacing;
+                }
+            }
+        }
+    }
+}
+
+/// A top-level widget with where it goes and which outputs show it.
+struct Item {
+    widget: Box<dyn Widget>,
+    anchor: Anchor,
+    offset: (f32, f32),
+    outputs: Option<Vec<String>>,
+}
+
+/// Everything drawn over the background, built from `[layout] widgets`.
+/// Each name there is a section whose `type` (the section name by default)
+/// is `battery`, `clock`, `command`, `indicator`, `keyboard`, `keypad`,
+/// `message`, `image`, `now_playing`, `user`, `column` or `row`.
+pub struct Layout {
+    items: Vec<Item>,
+}
+
+impl Layout {
+    pub fn from_config(conf: &Config) -> Self {
+        let names = match conf.get(LAYOUT_SECTION, "widgets") {
+            Some(widgets) => list(widgets),
+            // Without a layout, show the user, clock and battery when they
+            // are configured, the indicator, the message area, the
+            // keyboard state and the keypad, which stays hidden until it
+            // is needed.
+            None => [
+                "user",
+                "clock",
+                "battery",
+                "indicator",
+                "message",
+                "keyboard",
+                "keypad",
+            ]
+            .into_iter()
+            .filter(|name| {
+                !matches!(*name, "user" | "clock" | "battery") || conf.section(name).is_some()
+            })
+            .map(str::to_string)
+            .collect(),
+        };
+
+        let items = names
+            .iter()
+            .filter_map(|name| {
+                let (default_anchor, default_offset) = defaults(kind(conf, name));
+                let (anchor, offset) = placement(conf, name, default_anchor, default_offset);
+                let widget = build(conf, name, anchor, &mut Vec::new())?;
+                Some(Item {
+                    widget,
+                    anchor,
+                    offset,
+                    outputs: conf.get(name, "outputs").map(list),
+                })
+            })
+            .collect();
+        Self { items }
+    }
+
+    /// Updates every widget and returns the time until the next one wants to
+    /// be updated.
+    pub fn update(&mut self, events: &LoopHandle<'static, AppData>) -> Option<Duration> {
+        self.items
+            .iter_mut()
+            .fold(None, |next, item| sooner(next, item.widget.update(events)))
+    }
+
+    /// Gives `keysym` to the first widget that wants it, returning whether
+    /// one did.
+    pub fn key(&mut self, keysym: Keysym) -> bool {
+        self.items.iter_mut().any(|item| item.widget.key(keysym))
+    }
+
+    /// Adds the widgets shown on `output` to `overlay`.
+    pub fn draw(&mut self, frame: &mut Frame, output: Option<&str>, overlay: &mut Overlay) {
+        for item in &mut self.items {
+            if let Some(outputs) = &item.outputs {
+                if !output.is_some_and(|output| outputs.iter().any(|o| o == output)) {
+                    continue;
+                }
+            }
+            let size = item.widget.layout(frame);
+            if size.0 <= 0.0 || size.1 <= 0.0 {
+                continue;
+            }
+            let offset = (frame.scaled(item.offset.0), frame.scaled(item.offset.1));
+            let origin = item.anchor.place(frame.surface_size, size, offset);
+            item.widget.draw(frame, origin, size, overlay);
+        }
+    }
+}
+
+fn kind<'a>(conf: &'a Config, name: &'a str) -> &'a str {
+    conf.get(name, "type").unwrap_or(name)
+}
+
+fn defaults(kind: &str) -> (Anchor, (f32, f32)) {
+    match kind {
+        "battery" => (battery::ANCHOR_DEFAULT, battery::OFFSET_DEFAULT),
+        "clock" => (clock::ANCHOR_DEFAULT, clock::OFFSET_DEFAULT),
+        "command" => (command::ANCHOR_DEFAULT, command::OFFSET_DEFAULT),
+        "indicator" => (indicator::ANCHOR_DEFAULT, indicator::OFFSET_DEFAULT),
+        "keyboard" => (keyboard::ANCHOR_DEFAULT, keyboard::OFFSET_DEFAULT),
+        "keypad" => (keypad::ANCHOR_DEFAULT, keypad::OFFSET_DEFAULT),
+        "message" => (message::ANCHOR_DEFAULT, message::OFFSET_DEFAULT),
+        "image" => (image::ANCHOR_DEFAULT, image::OFFSET_DEFAULT),
+        "now_playing" => (now_playing::ANCHOR_DEFAULT, now_playing::OFFSET_DEFAULT),
+        "user" => (user::ANCHOR_DEFAULT, user::OFFSET_DEFAULT),
+        _ => (Anchor::Center, (0.0, 0.0)),
+    }
+}
+
+/// Creates the widget described by section `name`. `parents` holds the
+/// groups being built around it so a group cannot contain itself.
+fn build(
+    conf: &Config,
+    name: &str,
+    anchor: Anchor,
+    parents: &mut Vec<String>,
+) -> Option<Box<dyn Widget>> {
+    match kind(conf, name) {
+        "battery" => Some(Box::new(Battery::from_config(conf, name, anchor)?)),
+        "clock" => Some(Box::new(Clock::from_config(conf, name, anchor)?)),
+        "command" => Some(Box::new(CommandWidget::from_config(conf, name, anchor)?)),
+        "indicator" => Some(Box::new(Indicator::from_config(conf, name)?)),
+        "keyboard" => Some(Box::new(Keyboard::from_config(conf, name, anchor)?)),
+        "keypad" => Some(Box::new(Keypad::from_config(conf, name)?)),
+        "message" => Some(Box::new(Message::from_config(conf, name, anchor)?)),
+        "image" => Some(Box::new(ImageWidget::from_config(conf, name)?)),
+        "now_playing" => Some(Box::new(NowPlaying::from_config(conf, name, anchor)?)),
+        "user" => Some(Box::new(User::from_config(conf, name, anchor)?)),
+        kind @ ("column" | "row") => {
+            if !value(conf, name, "enabled", true) {
+                return None;
+            }
+            if parents.iter().any(|parent| parent == name) {
+                tracing::warn!("[{}] contains itself, ignoring it there", name);
+                return None;
+            }
+            parents.push(name.to_string());
+            let children = conf
+                .get(name, "children")
+                .map(list)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|child| build(conf, child, anchor, parents))
+                .collect();
+            parents.pop();
+            Some(Box::new(Group {
+                direction: if kind == "row" {
+                    Direction::Row
+                } else {
+                    Direction::Column
+                },
+                spacing: value(conf, name, "spacing", SPACING_DEFAULT),
+                align: anchor.align(),
+                children,
+                sizes: Vec::new(),
+            }))
+        }
+        other => {
+            tracing::warn!("Unknown widget type '{}' for [{}]", other, name);
+            None
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::shapes::Shape;
+use crate::graphics::text::{TextSection, TextStyle};
+
+use super::{align_x, color, text_style, value, Anchor, Frame, Widget};
+
+const SECTION: &str = "message";
+const SIZE_DEFAULT: f32 = 14.0;
+const MAX_WIDTH_DEFAULT: f32 = 600.0;
+const TIMEOUT_DEFAULT: u64 = 5;
+const PADDING_DEFAULT: f32 = 12.0;
+const CORNER_RADIUS_DEFAULT: f32 = 8.0;
+pub const ANCHOR_DEFAULT: Anchor = Anchor::Bottom;
+pub const OFFSET_DEFAULT: (f32, f32) = (0.0, -200.0);
+
+/// How long authentication feedback stays up, from `[message] timeout`, or
+/// `None` to keep it until the next attempt.
+pub fn feedback_timeout(conf: &Config) -> Option<Duration> {
+    let timeout = value(conf, SECTION, "timeout", TIMEOUT_DEFAULT);
+    (timeout > 0).then(|| Duration::from_secs(timeout))
+}
+
+/// A fixed notice from `text` with feedback from authentication, such as
+/// PAM errors, listed underneath it for a while.
+pub struct Message {
+    text: Option<String>,
+    style: TextStyle,
+    feedback_style: TextStyle,
+    show_feedback: bool,
+    max_width: f32,
+    background: Option<wgpu::Color>,
+    padding: f32,
+    corner_radius: f32,
+    sections: Vec<(TextSection, (f32, f32))>,
+}
+
+impl Message {
+    /// Builds a message area from `section`. It exists even without a
+    /// `text` so feedback has somewhere to go; `enabled=false` drops both,
+    /// and `feedback=false` leaves just the text.
+    pub fn from_config(conf: &Config, section: &str, anchor: Anchor) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        Some(Self {
+            text: conf
+                .get(section, "text")
+                .filter(|t| !t.trim().is_empty())
+                .map(str::to_string),
+            style: text_style(conf, section, "", SIZE_DEFAULT, anchor.align()),
+            feedback_style: text_style(conf, section, "feedback_", SIZE_DEFAULT, anchor.align()),
+            show_feedback: value(conf, section, "feedback", true),
+            max_width: value(conf, section, "max_width", MAX_WIDTH_DEFAULT),
+            background: conf
+                .get(section, "background")
+                .map(|_| color(conf, section, "background", wgpu::Color::TRANSPARENT)),
+            padding: value(conf, section, "padding", PADDING_DEFAULT),
+            corner_radius: value(conf, section, "corner_radius", CORNER_RADIUS_DEFAULT),
+            sections: Vec::new(),
+        })
+    }
+}
+
+impl Widget for Message {
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        let scale = frame.scale_factor as f32;
+        let max_width = frame.scaled(self.max_width);
+        let feedback = if self.show_feedback {
+            frame.lock.feedback
+        } else {
+            &[]
+        };
+        let prompt = frame.lock.fingerprint.filter(|_| self.show_feedback);
+        self.sections = self
+            .text
+            .iter()
+            .map(|t| (t.as_str(), &self.style))
+            .chain(prompt.map(|p| (p, &self.feedback_style)))
+            .chain(feedback.iter().map(|f| (f.as_str(), &self.feedback_style)))
+            .map(|(t, style)| {
+                let section = TextSection {
+                    text: frame.text.wrap(t, style, max_width, scale),
+                    style: style.clone(),
+                    position: (0.0, 0.0),
+                };
+                let size = frame.text.measure(&section, scale);
+                (section, size)
+            })
+            .collect();
+        self.sections.iter().fold((0.0f32, 0.0f32), |acc, (_, s)| {
+            (acc.0.max(s.0), acc.1 + s.1)
+        })
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        if self.sections.is_empty() {
+            return;
+        }
+        if let Some(background) = self.background {
+            let padding = frame.scaled(self.padding);
+            overlay.shapes.push(Shape::Rect {
+                position: (origin.0 - padding, origin.1 - padding),
+                size: (size.0 + padding * 2.0, size.1 + padding * 2.0),
+                corner_radius: frame.scaled(self.corner_radius),
+                color: background,
+            });
+        }
+
+        let mut y = origin.1;
+        for (mut section, line_size) in self.sections.drain(..) {
+            section.position = (align_x(section.style.align, origin.0, size.0), y);
+            y += line_size.1;
+            overlay.texts.push(section);
+        }
+    }
+}
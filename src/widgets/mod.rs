@@ -0,0 +1,245 @@
+pub mod battery;
+pub mod clock;
+pub mod command;
+pub mod image;
+pub mod indicator;
+pub mod keyboard;
+pub mod keypad;
+pub mod layout;
+pub mod message;
+pub mod now_playing;
+pub mod user;
+
+use std::{str::FromStr, time::Duration};
+
+use smithay_client_toolkit::{reexports::calloop::LoopHandle, seat::keyboard::Keysym};
+
+use crate::app::AppData;
+use crate::config::{parse_color, Config};
+use crate::graphics::overlay::Overlay;
+use crate::graphics::text::{Align, TextRenderer, TextStyle};
+use crate::lock::AuthState;
+
+const FONT_DEFAULT: &str = "sans-serif";
+const COLOR_DEFAULT: wgpu::Color = wgpu::Color::WHITE;
+
+/// What clicking a widget does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    CycleLayout,
+    /// Shows or hides the typed password.
+    ToggleReveal,
+    /// Switches the on-screen keys between lower and upper case.
+    Shift,
+    /// Acts like pressing the key, for widgets that already handle one.
+    Key(Keysym),
+}
+
+/// The parts of the lock state widgets react to.
+pub struct LockView<'a> {
+    pub auth_state: AuthState,
+    pub password_length: usize,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    /// Name of the active keyboard layout and how many the keymap has.
+    pub layout: Option<&'a str>,
+    pub layouts: u32,
+    pub feedback: &'a [String],
+    /// Prompt shown while a fingerprint reader is waiting for a finger.
+    pub fingerprint: Option<&'a str>,
+    /// The password, while it is revealed.
+    pub revealed: Option<&'a str>,
+    pub physical_keyboard: bool,
+    /// Whether the screen has been touched since locking.
+    pub touched: bool,
+    pub keypad_shift: bool,
+}
+
+/// One surface being drawn: sizes are in physical pixels.
+pub struct Frame<'a> {
+    pub text: &'a mut TextRenderer,
+    pub lock: &'a LockView<'a>,
+    pub surface_size: (u32, u32),
+    pub scale_factor: u32,
+}
+
+impl Frame<'_> {
+    /// Converts logical pixels to physical ones on this surface.
+    pub fn scaled(&self, logical: f32) -> f32 {
+        logical * self.scale_factor as f32
+    }
+}
+
+/// Something drawn on top of the background. The layout calls `layout` and
+/// then `draw` with a box of the size it returned, once per surface and
+/// frame.
+pub trait Widget {
+    /// Refreshes anything that changes with time and returns how long until
+    /// it should be called again, or `None` if it never needs to be. It can
+    /// be called earlier than asked when another widget is due. Work that
+    /// may take a while goes on `events` rather than blocking here.
+    fn update(&mut self, _events: &LoopHandle<'static, AppData>) -> Option<Duration> {
+        None
+    }
+
+    /// Offers the widget a key press before it goes to the password.
+    /// Returns whether the widget used it.
+    fn key(&mut self, _keysym: Keysym) -> bool {
+        false
+    }
+
+    /// Size the widget needs in physical pixels; `(0.0, 0.0)` hides it.
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32);
+
+    /// Adds the widget to `overlay` inside the box at `origin`.
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    );
+}
+
+/// x for text with `align` inside a box starting at `left` of `width`.
+pub fn align_x(align: Align, left: f32, width: f32) -> f32 {
+    match align {
+        Align::Left => left,
+        Align::Center => left + width / 2.0,
+        Align::Right => left + width,
+    }
+}
+
+/// Where on the surface a widget sits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl FromStr for Anchor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Anchor::TopLeft),
+            "top" => Ok(Anchor::Top),
+            "top-right" => Ok(Anchor::TopRight),
+            "left" => Ok(Anchor::Left),
+            "center" => Ok(Anchor::Center),
+            "right" => Ok(Anchor::Right),
+            "bottom-left" => Ok(Anchor::BottomLeft),
+            "bottom" => Ok(Anchor::Bottom),
+            "bottom-right" => Ok(Anchor::BottomRight),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Anchor {
+    /// Text alignment that keeps content against the anchored edge.
+    pub fn align(&self) -> Align {
+        match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => Align::Left,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => Align::Center,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => Align::Right,
+        }
+    }
+
+    /// Top left corner of a block of `size` anchored on a surface of
+    /// `surface` size, both in physical pixels.
+    pub fn place(&self, surface: (u32, u32), size: (f32, f32), offset: (f32, f32)) -> (f32, f32) {
+        let (width, height) = (surface.0 as f32, surface.1 as f32);
+        let x = match self.align() {
+            Align::Left => 0.0,
+            Align::Center => (width - size.0) / 2.0,
+            Align::Right => width - size.0,
+        };
+        let y = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0.0,
+            Anchor::Left | Anchor::Center | Anchor::Right => (height - size.1) / 2.0,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => height - size.1,
+        };
+        (x + offset.0, y + offset.1)
+    }
+}
+
+/// Reads a widget's anchor and its `offset_x`/`offset_y` in logical pixels.
+pub fn placement(
+    conf: &Config,
+    section: &str,
+    default: Anchor,
+    default_offset: (f32, f32),
+) -> (Anchor, (f32, f32)) {
+    let anchor = conf
+        .get(section, "anchor")
+        .map(|s| {
+            s.parse::<Anchor>().unwrap_or_else(|_| {
+                tracing::warn!("Unknown anchor '{}' in [{}]", s, section);
+                default
+            })
+        })
+        .unwrap_or(default);
+    let offset = |key, default| {
+        conf.get(section, key)
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(default)
+    };
+    (
+        anchor,
+        (
+            offset("offset_x", default_offset.0),
+            offset("offset_y", default_offset.1),
+        ),
+    )
+}
+
+/// Parses a key with `FromStr`, falling back to `default` when it is
+/// missing or malformed.
+pub fn value<T: FromStr>(conf: &Config, section: &str, key: &str, default: T) -> T {
+    conf.get(section, key)
+        .and_then(|v| v.parse::<T>().ok())
+        .unwrap_or(default)
+}
+
+/// Reads a color key, warning about and replacing values that don't parse.
+pub fn color(conf: &Config, section: &str, key: &str, default: wgpu::Color) -> wgpu::Color {
+    conf.get(section, key)
+        .map(|v| {
+            parse_color(v).unwrap_or_else(|| {
+                tracing::warn!("Invalid color '{}' in [{}] {}", v, section, key);
+                default
+            })
+        })
+        .unwrap_or(default)
+}
+
+/// Reads `<prefix>font`, `<prefix>size` and `<prefix>color` from `section`.
+pub fn text_style(
+    conf: &Config,
+    section: &str,
+    prefix: &str,
+    default_size: f32,
+    align: Align,
+) -> TextStyle {
+    let key = |name: &str| format!("{}{}", prefix, name);
+    TextStyle {
+        font: conf
+            .get(section, &key("font"))
+            .unwrap_or(FONT_DEFAULT)
+            .to_string(),
+        size: conf
+            .get(section, &key("size"))
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(default_size),
+        color: color(conf, section, &key("color"), COLOR_DEFAULT),
+        align,
+    }
+}
@@ -0,0 +1,347 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc, thread, time::Duration};
+
+use image::RgbaImage;
+use smithay_client_toolkit::{
+    reexports::calloop::{channel, LoopHandle},
+    seat::keyboard::Keysym,
+};
+
+use crate::app::AppData;
+use crate::config::Config;
+use crate::graphics::overlay::{Overlay, Target};
+use crate::graphics::sprites::Sprite;
+use crate::graphics::text::{Align, TextSection, TextStyle};
+use crate::media::image_media::decode;
+use crate::mpris::{Control, Mpris, Player};
+
+use super::{align_x, text_style, value, Action, Anchor, Frame, Widget};
+
+const ART_SIZE_DEFAULT: f32 = 64.0;
+const TITLE_SIZE_DEFAULT: f32 = 16.0;
+const ARTIST_SIZE_DEFAULT: f32 = 13.0;
+const SPACING_DEFAULT: f32 = 12.0;
+pub const ANCHOR_DEFAULT: Anchor = Anchor::BottomLeft;
+pub const OFFSET_DEFAULT: (f32, f32) = (20.0, -20.0);
+
+/// Path of a `file://` URL. Art behind any other scheme would mean fetching
+/// it from the lock screen, so it isn't shown.
+fn file_path(url: &str) -> Option<PathBuf> {
+    let encoded = url.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escaped = (encoded[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            None => {
+                bytes.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+/// Art for the last URL seen, loaded for outputs up to `scale`. Without an
+/// image while it first loads or if it couldn't be loaded.
+struct Art {
+    url: String,
+    scale: u32,
+    image: Option<Rc<RgbaImage>>,
+}
+
+/// The track playing in an MPRIS media player, with its cover art. Media
+/// keys control the player without unlocking, and clicking the widget
+/// plays or pauses it.
+pub struct NowPlaying {
+    name: String,
+    player: Option<String>,
+    controls: bool,
+    art_size: f32,
+    spacing: f32,
+    title_style: TextStyle,
+    artist_style: TextStyle,
+    mpris: Option<Mpris>,
+    /// Set once connected, to hand decoded art back to.
+    events: Option<LoopHandle<'static, AppData>>,
+    art: Rc<RefCell<Option<Art>>>,
+    /// Player shown in the last layout, which media keys go to.
+    shown: Option<String>,
+    sections: Vec<(TextSection, (f32, f32))>,
+    text_size: (f32, f32),
+}
+
+impl NowPlaying {
+    /// Builds the widget from `section`. `player` limits it to players
+    /// whose name contains that text, and `controls=false` leaves media
+    /// keys alone.
+    pub fn from_config(conf: &Config, section: &str, anchor: Anchor) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        // The art sits on the left, so text is only right aligned when the
+        // widget is on the right of the screen.
+        let align = match anchor.align() {
+            Align::Right => Align::Right,
+            _ => Align::Left,
+        };
+        Some(Self {
+            name: section.to_string(),
+            player: conf.get(section, "player").map(str::to_string),
+            controls: value(conf, section, "controls", true),
+            art_size: value(conf, section, "art_size", ART_SIZE_DEFAULT),
+            spacing: value(conf, section, "spacing", SPACING_DEFAULT),
+            title_style: text_style(conf, section, "title_", TITLE_SIZE_DEFAULT, align),
+            artist_style: text_style(conf, section, "artist_", ARTIST_SIZE_DEFAULT, align),
+            mpris: None,
+            events: None,
+            art: Rc::default(),
+            shown: None,
+            sections: Vec::new(),
+            text_size: (0.0, 0.0),
+        })
+    }
+
+    /// The player to show: the first one playing, or else the first with a
+    /// track loaded.
+    fn active(&self) -> Option<Player> {
+        let players = self.mpris.as_ref()?.players();
+        let matching = || {
+            players.iter().filter(|p| {
+                self.player
+                    .as_ref()
+                    .is_none_or(|filter| p.short_name().contains(filter.as_str()))
+            })
+        };
+        matching()
+            .find(|p| p.playing && p.track.title.is_some())
+            .or_else(|| matching().find(|p| p.track.title.is_some()))
+            .cloned()
+    }
+
+    /// The art at `url`, once loaded. A new URL, or an output with a larger
+    /// `scale` than it was loaded for, starts decoding it on its own thread,
+    /// as large covers take a while.
+    fn art(&mut self, url: Option<&str>, scale: u32) -> Option<Rc<RgbaImage>> {
+        let Some(url) = url else {
+            *self.art.borrow_mut() = None;
+            return None;
+        };
+        let image = match &mut *self.art.borrow_mut() {
+            Some(art) if art.url == url && art.scale >= scale => return art.image.clone(),
+            // The smaller art is shown until the sharper one is ready.
+            Some(art) if art.url == url => {
+                art.scale = scale;
+                art.image.clone()
+            }
+            art => {
+                *art = Some(Art {
+                    url: url.to_string(),
+                    scale,
+                    image: None,
+                });
+                None
+            }
+        };
+        if let (Some(events), Some(path)) = (&self.events, file_path(url)) {
+            self.load_art(events, url.to_string(), scale, path);
+        }
+        image
+    }
+
+    fn load_art(
+        &self,
+        events: &LoopHandle<'static, AppData>,
+        url: String,
+        scale: u32,
+        path: PathBuf,
+    ) {
+        let (sender, receiver) = channel::channel();
+        let art = self.art.clone();
+        // The source goes away by itself once the thread drops the sender.
+        let inserted = events.insert_source(receiver, move |event, _, app_data| {
+            let channel::Event::Msg(image) = event else {
+                return;
+            };
+            // Unless the track or the scale changed again meanwhile.
+            let current = match art.borrow_mut().as_mut() {
+                Some(art) if art.url == url && art.scale == scale => {
+                    art.image = Some(Rc::new(image));
+                    true
+                }
+                _ => false,
+            };
+            if current {
+                app_data.render_all();
+            }
+        });
+        if let Err(e) = inserted {
+            tracing::warn!("[{}] unable to load art, {}", self.name, e);
+            return;
+        }
+        let name = self.name.clone();
+        let side = (self.art_size * scale as f32).ceil() as u32;
+        thread::spawn(move || match decode(&path, (side, side)) {
+            Ok(image) => {
+                let _ = sender.send(image);
+            }
+            Err(e) => tracing::warn!("[{}] unable to open art {:?}, {}", name, path, e),
+        });
+    }
+}
+
+impl Widget for NowPlaying {
+    fn update(&mut self, events: &LoopHandle<'static, AppData>) -> Option<Duration> {
+        // Players are followed through their signals from here on.
+        if self.events.is_none() {
+            self.events = Some(events.clone());
+            match Mpris::connect(events) {
+                Ok(mpris) => self.mpris = Some(mpris),
+                Err(e) => tracing::warn!("[{}] no session bus, {}", self.name, e),
+            }
+        }
+        None
+    }
+
+    fn key(&mut self, keysym: Keysym) -> bool {
+        let control = match keysym {
+            Keysym::XF86_AudioPlay => Control::PlayPause,
+            Keysym::XF86_AudioPause => Control::Pause,
+            Keysym::XF86_AudioStop => Control::Stop,
+            Keysym::XF86_AudioNext => Control::Next,
+            Keysym::XF86_AudioPrev => Control::Previous,
+            _ => return false,
+        };
+        match (&self.mpris, &self.shown) {
+            (Some(mpris), Some(player)) if self.controls => {
+                mpris.control(player, control);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        self.sections.clear();
+        let Some(player) = self.active() else {
+            self.shown = None;
+            return (0.0, 0.0);
+        };
+        let has_art = self
+            .art(player.track.art_url.as_deref(), frame.scale_factor)
+            .is_some();
+        self.shown = Some(player.name);
+
+        let lines = [
+            (player.track.title, &self.title_style),
+            (player.track.artist, &self.artist_style),
+        ];
+        for (text, style) in lines {
+            let Some(text) = text else { continue };
+            let section = TextSection {
+                text,
+                style: style.clone(),
+                position: (0.0, 0.0),
+            };
+            let size = frame.text.measure(&section, frame.scale_factor as f32);
+            self.sections.push((section, size));
+        }
+        self.text_size = self
+            .sections
+            .iter()
+            .fold((0.0, 0.0), |(w, h), (_, s)| (f32::max(w, s.0), h + s.1));
+
+        let (art, gap) = if has_art {
+            (frame.scaled(self.art_size), frame.scaled(self.spacing))
+        } else {
+            (0.0, 0.0)
+        };
+        (art + gap + self.text_size.0, art.max(self.text_size.1))
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        if self.controls {
+            overlay.targets.push(Target {
+                position: origin,
+                size,
+                action: Action::Key(Keysym::XF86_AudioPlay),
+            });
+        }
+        let mut left = origin.0;
+        let image = self.art.borrow().as_ref().and_then(|art| art.image.clone());
+        if let Some(image) = image {
+            // Fit the art into a square, keeping its shape.
+            let side = frame.scaled(self.art_size);
+            let (width, height) = (image.width() as f32, image.height() as f32);
+            let scale = side / width.max(height);
+            let art = (width * scale, height * scale);
+            overlay.sprites.push(Sprite {
+                image,
+                position: (
+                    left + (side - art.0) / 2.0,
+                    origin.1 + (size.1 - art.1) / 2.0,
+                ),
+                size: art,
+                opacity: 1.0,
+            });
+            left += side + frame.scaled(self.spacing);
+        }
+
+        let mut y = origin.1 + (size.1 - self.text_size.1) / 2.0;
+        let width = origin.0 + size.0 - left;
+        for (mut section, section_size) in self.sections.drain(..) {
+            section.position = (align_x(section.style.align, left, width), y);
+            y += section_size.1;
+            overlay.texts.push(section);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_file_urls() {
+        assert_eq!(
+            file_path("file:///home/me/My%20Music/caf%C3%A9.jpg"),
+            Some(PathBuf::from("/home/me/My Music/café.jpg"))
+        );
+        assert_eq!(
+            file_path("file:///tmp/cover.png"),
+            Some(PathBuf::from("/tmp/cover.png"))
+        );
+    }
+
+    #[test]
+    fn keeps_stray_percent_signs() {
+        assert_eq!(
+            file_path("file:///tmp/100%.png"),
+            Some(PathBuf::from("/tmp/100%.png"))
+        );
+        assert_eq!(
+            file_path("file:///tmp/%zz.png"),
+            Some(PathBuf::from("/tmp/%zz.png"))
+        );
+    }
+
+    #[test]
+    fn refuses_other_schemes() {
+        assert_eq!(file_path("https://example.com/cover.png"), None);
+        assert_eq!(file_path("/tmp/cover.png"), None);
+        // Not UTF-8 once decoded.
+        assert_eq!(file_path("file:///tmp/%FF.png"), None);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, path::PathBuf, rc::Rc};
+
+use image::{
+    imageops::{self, FilterType},
+    RgbaImage,
+};
+
+use crate::config::Config;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::sprites::Sprite;
+use crate::graphics::text::{Align, TextSection, TextStyle};
+use crate::media::image_media::decode;
+
+use super::{align_x, text_style, value, Anchor, Frame, Widget};
+
+const ACCOUNTS_SERVICE_ICONS: &str = "/var/lib/AccountsService/icons";
+const AVATAR_SIZE_DEFAULT: f32 = 96.0;
+const NAME_SIZE_DEFAULT: f32 = 18.0;
+const SPACING_DEFAULT: f32 = 12.0;
+pub const ANCHOR_DEFAULT: Anchor = Anchor::Center;
+pub const OFFSET_DEFAULT: (f32, f32) = (0.0, -160.0);
+
+/// First avatar found among `~/.face` and AccountsService's icon for
+/// `username`.
+fn default_avatar(username: &str) -> Option<PathBuf> {
+    let home = std::env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".face"));
+    home.into_iter()
+        .chain(std::iter::once(
+            PathBuf::from(ACCOUNTS_SERVICE_ICONS).join(username),
+        ))
+        .find(|path| path.is_file())
+}
+
+/// Scales `image` to cover a `diameter` square, crops the middle and cuts a
+/// circle out of it with a one pixel soft edge.
+fn circle_crop(image: &RgbaImage, diameter: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let scale = diameter as f32 / width.min(height) as f32;
+    let scaled_width = ((width as f32 * scale).round() as u32).max(diameter);
+    let scaled_height = ((height as f32 * scale).round() as u32).max(diameter);
+    let scaled = imageops::resize(image, scaled_width, scaled_height, FilterType::Lanczos3);
+    let mut cropped = imageops::crop_imm(
+        &scaled,
+        (scaled_width - diameter) / 2,
+        (scaled_height - diameter) / 2,
+        diameter,
+        diameter,
+    )
+    .to_image();
+
+    let radius = diameter as f32 / 2.0;
+    for (x, y, pixel) in cropped.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - radius;
+        let dy = y as f32 + 0.5 - radius;
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+    cropped
+}
+
+/// The locked user's avatar with their real name underneath, so it is
+/// obvious whose session this is.
+pub struct User {
+    avatar: Option<RgbaImage>,
+    /// Cropped avatars by output scale factor.
+    scaled: HashMap<u32, Rc<RgbaImage>>,
+    avatar_size: f32,
+    name: Option<String>,
+    style: TextStyle,
+    spacing: f32,
+    section: Option<(TextSection, (f32, f32))>,
+}
+
+impl User {
+    /// Builds the widget from `section`. The avatar comes from `avatar`, or
+    /// else `~/.face` or AccountsService; `avatar=false` or `name=false`
+    /// hide either part.
+    pub fn from_config(conf: &Config, section: &str, anchor: Anchor) -> Option<Self> {
+        if !value(conf, section, "enabled", true) {
+            return None;
+        }
+        let username = whoami::username();
+        let avatar_path = match conf.get(section, "avatar") {
+            Some("false") => None,
+            Some(_) => match conf.get_path(section, "avatar") {
+                Some(Ok(path)) => Some(path),
+                Some(Err(e)) => {
+                    tracing::warn!("No avatar for [{}]: {}", section, e);
+                    None
+                }
+                None => None,
+            },
+            None => default_avatar(&username),
+        };
+        let avatar = avatar_path.and_then(|path| match decode(&path, (0, 0)) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                tracing::warn!("Unable to open avatar {:?}, {}", path, e);
+                None
+            }
+        });
+
+        let name = match conf.get(section, "name") {
+            Some("false") => None,
+            Some(name) => Some(name.to_string()),
+            // whoami takes the full name from the GECOS field and falls
+            // back to the login name when it is empty.
+            None => Some(whoami::realname()),
+        }
+        .filter(|name| !name.trim().is_empty());
+
+        Some(Self {
+            avatar,
+            scaled: HashMap::new(),
+            avatar_size: value(conf, section, "avatar_size", AVATAR_SIZE_DEFAULT),
+            name,
+            style: text_style(conf, section, "", NAME_SIZE_DEFAULT, anchor.align()),
+            spacing: value(conf, section, "spacing", SPACING_DEFAULT),
+            section: None,
+        })
+    }
+
+    /// The avatar cropped for outputs with `scale_factor`.
+    fn avatar(&mut self, scale_factor: u32) -> Option<Rc<RgbaImage>> {
+        let avatar = self.avatar.as_ref()?;
+        let diameter = ((self.avatar_size * scale_factor as f32).round() as u32).max(1);
+        Some(
+            self.scaled
+                .entry(scale_factor)
+                .or_insert_with(|| Rc::new(circle_crop(avatar, diameter)))
+                .clone(),
+        )
+    }
+}
+
+impl Widget for User {
+    fn layout(&mut self, frame: &mut Frame) -> (f32, f32) {
+        let avatar = self
+            .avatar
+            .as_ref()
+            .map(|_| frame.scaled(self.avatar_size))
+            .unwrap_or(0.0);
+        self.section = self.name.as_ref().map(|name| {
+            let section = TextSection {
+                text: name.clone(),
+                style: self.style.clone(),
+                position: (0.0, 0.0),
+            };
+            let size = frame.text.measure(&section, frame.scale_factor as f32);
+            (section, size)
+        });
+        let (text_width, text_height) = self.section.as_ref().map_or((0.0, 0.0), |(_, s)| *s);
+        let gap = if avatar > 0.0 && text_height > 0.0 {
+            frame.scaled(self.spacing)
+        } else {
+            0.0
+        };
+        (avatar.max(text_width), avatar + gap + text_height)
+    }
+
+    fn draw(
+        &mut self,
+        frame: &mut Frame,
+        origin: (f32, f32),
+        size: (f32, f32),
+        overlay: &mut Overlay,
+    ) {
+        let mut y = origin.1;
+        if let Some(image) = self.avatar(frame.scale_factor) {
+            let diameter = frame.scaled(self.avatar_size);
+            let x = match self.style.align {
+                Align::Left => origin.0,
+                Align::Center => origin.0 + (size.0 - diameter) / 2.0,
+                Align::Right => origin.0 + size.0 - diameter,
+            };
+            overlay.sprites.push(Sprite {
+                image,
+                position: (x, y),
+                size: (diameter, diameter),
+                opacity: 1.0,
+            });
+            y += diameter + frame.scaled(self.spacing);
+        }
+        if let Some((mut section, _)) = self.section.take() {
+            section.position = (align_x(section.style.align, origin.0, size.0), y);
+            overlay.texts.push(section);
+        }
+    }
+}